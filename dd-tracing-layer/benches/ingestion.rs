@@ -0,0 +1,41 @@
+//! Throughput of the Datadog layer, from `tracing::info!` to a local mock of the Datadog API.
+//!
+//! Every iteration creates the layer, emits the logs and drops it, which waits for the last batch
+//! to be sent. It only uses the public API of the crate, so it can be run against older versions.
+//! The results are recorded in the README of the repository.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dd_tracing_layer::DatadogOptions;
+use tracing_subscriber::prelude::*;
+
+const LOGS: usize = 10_000;
+
+fn datadog(c: &mut Criterion) {
+    let server = httpmock::MockServer::start();
+    server.mock(|when, then| {
+        when.any_request();
+        then.status(202);
+    });
+
+    let mut group = c.benchmark_group("datadog");
+    group.throughput(Throughput::Elements(LOGS as u64));
+    group.sample_size(20);
+
+    group.bench_function(BenchmarkId::new("layer_to_http", LOGS), |b| {
+        b.iter(|| {
+            let options = DatadogOptions::new("bench", "api-key").with_url(server.base_url());
+            let subscriber = tracing_subscriber::registry().with(dd_tracing_layer::create(options));
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("request", user = "rob").in_scope(|| {
+                    for i in 0..LOGS {
+                        tracing::info!(i, "hello");
+                    }
+                });
+            });
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, datadog);
+criterion_main!(benches);
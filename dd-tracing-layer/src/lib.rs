@@ -14,25 +14,37 @@
 //!   tracing::info!("your message: {}", msg);
 //! }
 //!
-//! fn main() {
-//!     let options = DatadogOptions::new("my-service", "my-datadog-api-key")
-//!         .with_tags("env:dev");
-//!     let dd = dd_tracing_layer::create(options);
-//!     let subscriber = tracing_subscriber::registry()
-//!         .with(tracing_subscriber::fmt::Layer::new().json())
-//!         .with(dd);
-//!     let _s = subscriber::set_default(subscriber);
-//!     log("hello world!");
-//! }
+//! let options = DatadogOptions::new("my-service", "my-datadog-api-key")
+//!     .with_tags("env:dev");
+//! let dd = dd_tracing_layer::create(options);
+//! let subscriber = tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::Layer::new().json())
+//!     .with(dd);
+//! let _s = subscriber::set_default(subscriber);
+//! log("hello world!");
 //!```
 mod datadog_ingestor;
 
-pub use datadog_ingestor::{DatadogOptions, Region};
-pub use log_tracing_layer::LogLayer;
+pub use datadog_ingestor::{DatadogLogIngestor, DatadogOptions, Region};
+pub use log_tracing_layer::{
+    crate_version, BytesEncoding, CallsiteFields, ContextProvider, Decision, EcsFormatter,
+    FieldFilter, JsonParsing, LargeIntegers, Log, LogLayer, LogLayerOptions, LogProcessor, Schema,
+    SecondsFormat, SpanEvents, SpanFormat, ThreadFields, TimestampFormat,
+};
+#[cfg(feature = "redaction")]
+pub use log_tracing_layer::{Pattern, RedactAction, Redactor};
 
 /// Creates a log layer that will send logs to Datadog
 #[must_use]
-pub fn create(options: DatadogOptions) -> LogLayer {
+pub fn create(mut options: DatadogOptions) -> LogLayer {
+    let mut layer_options = std::mem::take(&mut options.layer);
+    // Datadog parses RFC3339 dates with milliseconds
+    layer_options
+        .timestamp_format
+        .get_or_insert(TimestampFormat::Rfc3339 {
+            precision: SecondsFormat::Millis,
+            use_z: true,
+        });
     let ingestor = datadog_ingestor::DatadogLogIngestor::new(options);
-    LogLayer::new(ingestor)
+    LogLayer::with_options(ingestor, layer_options)
 }
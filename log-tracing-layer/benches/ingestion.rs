@@ -0,0 +1,82 @@
+//! Throughput of the layer, from `tracing::info!` to the ingestor.
+//!
+//! `legacy_ingestor` goes through a [`LogIngestor`], wrapped in a [`log_tracing_layer::LegacyIngestor`],
+//! which is the only path the layer offered before [`BatchLogIngestor`] existed, so it can be run
+//! against older versions of the crate. `batch_ingestor` goes through a [`BatchLogIngestor`].
+//! The results are recorded in the README of the repository.
+use async_trait::async_trait;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use log_tracing_layer::{BatchLogIngestor, FlushReport, IngestError, Log, LogIngestor, LogLayer};
+use tracing_subscriber::prelude::*;
+
+const LOGS: usize = 10_000;
+
+fn emit_logs() {
+    tracing::info_span!("request", user = "rob").in_scope(|| {
+        for i in 0..LOGS {
+            tracing::info!(i, "hello");
+        }
+    });
+}
+
+#[derive(Debug, Default)]
+struct NoopLegacyIngestor;
+
+#[async_trait]
+impl LogIngestor for NoopLegacyIngestor {
+    fn name(&self) -> &'static str {
+        "noop-legacy"
+    }
+
+    fn start(&self) {}
+
+    async fn ingest(&mut self, log: Log) {
+        criterion::black_box(log);
+    }
+
+    async fn flush(&mut self) {}
+}
+
+#[derive(Debug, Default)]
+struct NoopIngestor;
+
+#[async_trait]
+impl BatchLogIngestor for NoopIngestor {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    async fn ingest_batch(&mut self, logs: Vec<Log>) {
+        criterion::black_box(logs);
+    }
+
+    async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+        Ok(FlushReport::default())
+    }
+}
+
+fn layer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("layer");
+    group.throughput(Throughput::Elements(LOGS as u64));
+
+    // the subscriber is dropped at the end of every iteration, waiting for every log to be ingested
+    group.bench_function(BenchmarkId::new("legacy_ingestor", LOGS), |b| {
+        b.iter(|| {
+            let subscriber = tracing_subscriber::registry().with(LogLayer::new(NoopLegacyIngestor));
+            tracing::subscriber::with_default(subscriber, emit_logs);
+        });
+    });
+
+    group.bench_function(BenchmarkId::new("batch_ingestor", LOGS), |b| {
+        b.iter(|| {
+            let subscriber =
+                tracing_subscriber::registry().with(LogLayer::from_batch_ingestor(NoopIngestor));
+            tracing::subscriber::with_default(subscriber, emit_logs);
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, layer);
+criterion_main!(benches);
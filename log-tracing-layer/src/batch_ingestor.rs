@@ -0,0 +1,182 @@
+use crate::log_ingestor::{Log, LogIngestor};
+use crate::schema::Schema;
+use async_trait::async_trait;
+use std::{fmt, time::Duration};
+use tokio::task::JoinHandle;
+
+/// Summary of a flush operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    /// Number of logs successfully delivered.
+    pub sent: usize,
+    /// Number of logs that were given up on.
+    pub dropped: usize,
+}
+
+impl FlushReport {
+    #[must_use]
+    pub const fn merge(self, other: Self) -> Self {
+        Self {
+            sent: self.sent + other.sent,
+            dropped: self.dropped + other.dropped,
+        }
+    }
+}
+
+/// Error returned when logs can't be delivered.
+#[derive(Debug)]
+pub enum IngestError {
+    /// The logs couldn't be serialized or compressed.
+    Serialization(String),
+    /// The request couldn't reach the destination.
+    Transport(String),
+    /// The destination answered with a non-retriable status code.
+    Rejected { status: u16, message: String },
+    /// Some logs were dropped after exhausting the retries.
+    Incomplete(FlushReport),
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serialization(e) => write!(f, "failed to serialize logs: {e}"),
+            Self::Transport(e) => write!(f, "failed to send logs: {e}"),
+            Self::Rejected { status, message } => {
+                write!(f, "logs rejected with status {status}: {message}")
+            }
+            Self::Incomplete(report) => write!(
+                f,
+                "{} logs were dropped ({} sent)",
+                report.dropped, report.sent
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IngestError {}
+
+/// Handle to the background work started by a [`BatchLogIngestor`].
+///
+/// Cancelling the handle (or dropping it) aborts every task it owns.
+#[derive(Debug, Default)]
+pub struct IngestorHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl IngestorHandle {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ties a task to this handle.
+    pub fn push(&mut self, task: JoinHandle<()>) {
+        self.tasks.push(task);
+    }
+
+    /// Ties all the tasks of another handle to this one.
+    pub fn extend(&mut self, mut other: Self) {
+        self.tasks.append(&mut other.tasks);
+    }
+
+    /// Aborts all the tasks owned by this handle.
+    pub fn cancel(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+    }
+
+    /// Returns `true` if every task owned by this handle has finished.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.tasks.iter().all(JoinHandle::is_finished)
+    }
+}
+
+impl Drop for IngestorHandle {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Ingests logs in batches.
+///
+/// The [`crate::LogLayer`] drives the lifecycle of the ingestor from its own thread:
+///
+/// 1. [`BatchLogIngestor::accept_schema`] and [`BatchLogIngestor::start`] are called once.
+///    Any background task must be tied to the returned handle.
+/// 2. [`BatchLogIngestor::ingest_batch`] is called with the logs received so far.
+///    If [`BatchLogIngestor::flush_interval`] returns a period, [`BatchLogIngestor::flush`] is also called periodically.
+/// 3. When the layer is dropped, the handle is cancelled and [`BatchLogIngestor::shutdown`] is called.
+#[async_trait]
+pub trait BatchLogIngestor: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Offers the [`Schema`] of the layer to the ingestor. Returns `true` if the ingestor applies it itself,
+    /// e.g. a [`crate::Router`] whose directives read the original keys. Otherwise, the layer applies it.
+    fn accept_schema(&mut self, _schema: &Schema) -> bool {
+        false
+    }
+
+    fn start(&mut self) -> IngestorHandle {
+        IngestorHandle::default()
+    }
+
+    /// Period of the flush timer owned by the ingestion loop. `None` disables it.
+    fn flush_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    async fn ingest_batch(&mut self, logs: Vec<Log>);
+
+    async fn flush(&mut self) -> Result<FlushReport, IngestError>;
+
+    /// Flushes the pending logs before the ingestor is dropped. Only called once.
+    ///
+    /// It takes `&mut self` so that it's also called on boxed ingestors, like the routes of a [`crate::Router`].
+    async fn shutdown(&mut self) -> Result<FlushReport, IngestError> {
+        self.flush().await
+    }
+}
+
+/// Adapter turning a [`LogIngestor`] into a [`BatchLogIngestor`].
+///
+/// Note that the tasks spawned by [`LogIngestor::start`] are detached and can't be cancelled,
+/// and that [`LogIngestor::flush`] doesn't report anything, so the returned [`FlushReport`]s are empty.
+#[derive(Debug, Default)]
+pub struct LegacyIngestor<I> {
+    inner: I,
+}
+
+impl<I: LogIngestor> LegacyIngestor<I> {
+    pub const fn new(inner: I) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<I: LogIngestor> BatchLogIngestor for LegacyIngestor<I> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn start(&mut self) -> IngestorHandle {
+        self.inner.start();
+        IngestorHandle::default()
+    }
+
+    async fn ingest_batch(&mut self, logs: Vec<Log>) {
+        for log in logs {
+            self.inner.ingest(log).await;
+        }
+    }
+
+    async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+        self.inner.flush().await;
+        Ok(FlushReport::default())
+    }
+}
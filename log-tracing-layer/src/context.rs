@@ -0,0 +1,57 @@
+use serde_json::{json, Value};
+
+/// Detects a value added to every log. See [`crate::LogLayerOptions::with_context_provider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextProvider {
+    /// Name of the machine, under `host`.
+    Hostname,
+    /// Id of the process, under `pid`.
+    Pid,
+    /// File name of the current executable, under `executable`.
+    Executable,
+    /// Version of a crate, under `version`. Use [`crate::crate_version`] to get the one of your crate.
+    CrateVersion(&'static str),
+}
+
+impl ContextProvider {
+    /// Returns the key under which the provider value is added.
+    pub(crate) const fn key(&self) -> &'static str {
+        match self {
+            Self::Hostname => "host",
+            Self::Pid => "pid",
+            Self::Executable => "executable",
+            Self::CrateVersion(_) => "version",
+        }
+    }
+
+    /// Returns the key and the value of the provider, if it can be detected.
+    pub(crate) fn provide(&self) -> Option<(&'static str, Value)> {
+        match self {
+            Self::Hostname => {
+                let host = gethostname::gethostname().into_string().ok()?;
+                Some((self.key(), json!(host)))
+            }
+            Self::Pid => Some((self.key(), json!(std::process::id()))),
+            Self::Executable => {
+                let path = std::env::current_exe().ok()?;
+                let name = path.file_name()?.to_str()?.to_string();
+                Some((self.key(), json!(name)))
+            }
+            Self::CrateVersion(version) => Some((self.key(), json!(version))),
+        }
+    }
+}
+
+/// Provides the version of the crate calling the macro, under `version`.
+///
+/// ```rust
+/// use log_tracing_layer::{crate_version, LogLayerOptions};
+///
+/// let options = LogLayerOptions::new().with_context_provider(crate_version!());
+/// ```
+#[macro_export]
+macro_rules! crate_version {
+    () => {
+        $crate::ContextProvider::CrateVersion(env!("CARGO_PKG_VERSION"))
+    };
+}
@@ -0,0 +1,147 @@
+use crate::layer::insert_dotted;
+use crate::log_ingestor::Log;
+use crate::logged_error::LoggedError;
+use crate::options::LogLayerOptions;
+use crate::processor::{Decision, LogProcessor};
+use serde_json::{json, Map, Value};
+
+/// Version of the Elastic Common Schema the documents follow.
+pub const ECS_VERSION: &str = "8.11.0";
+
+/// Keys of the layer output mapped to ECS fields, shared with [`crate::Schema::ecs`].
+pub const MAPPINGS: &[(&str, &str)] = &[
+    ("message", "message"),
+    ("target", "log.logger"),
+    ("file", "log.origin.file.name"),
+    ("line", "log.origin.file.line"),
+    ("host", "host.hostname"),
+    ("pid", "process.pid"),
+    ("executable", "process.name"),
+    ("thread.name", "process.thread.name"),
+    ("thread.id", "process.thread.id"),
+    ("service", "service.name"),
+    ("version", "service.version"),
+    ("env", "service.environment"),
+];
+
+/// Converts the logs of the layer into [Elastic Common Schema](https://www.elastic.co/guide/en/ecs/current/ecs-field-reference.html) documents.
+///
+/// It can be used by any ingestor through [`EcsFormatter::format`], or as a [`LogProcessor`].
+/// Fields that are not part of ECS are kept as they are, or moved under a namespace with
+/// [`EcsFormatter::with_namespace`]. ECS fields take precedence over them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EcsFormatter {
+    service_name: Option<String>,
+    namespace: Option<String>,
+    timestamp_key: Option<String>,
+}
+
+impl EcsFormatter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a formatter reading the timestamp from the key the options write it to.
+    #[must_use]
+    pub fn from_options(options: &LogLayerOptions) -> Self {
+        Self {
+            timestamp_key: options.timestamp_key.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Key of the timestamp of the logs, `timestamp` by default.
+    #[must_use]
+    pub fn with_timestamp_key(mut self, timestamp_key: impl Into<String>) -> Self {
+        self.timestamp_key = Some(timestamp_key.into());
+        self
+    }
+
+    /// Sets `service.name`, which is otherwise taken from the `service` key of the log.
+    #[must_use]
+    pub fn with_service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.service_name = Some(service_name.into());
+        self
+    }
+
+    /// Moves the fields that are not part of ECS under the given key.
+    #[must_use]
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    #[must_use]
+    pub fn format(&self, mut log: Log) -> Log {
+        let mut ecs = Map::new();
+
+        if let Some(timestamp) = log.remove(self.timestamp_key.as_deref().unwrap_or("timestamp")) {
+            ecs.insert("@timestamp".to_string(), timestamp);
+        }
+        if let Some(level) = log.remove("level") {
+            let level = match level {
+                Value::String(level) => json!(level.to_lowercase()),
+                other => other,
+            };
+            insert_dotted(&mut ecs, "log.level", level);
+        }
+        for (key, field) in MAPPINGS {
+            if let Some(value) = log.remove(*key) {
+                insert_dotted(&mut ecs, field, value);
+            }
+        }
+        if let Some(service_name) = &self.service_name {
+            insert_dotted(&mut ecs, "service.name", json!(service_name));
+        }
+
+        // OpenTelemetry or remote ids first, then the ones of the layer
+        for (ecs_key, layer_key) in [("trace.id", "trace_id"), ("span.id", "span_id")] {
+            let layer_id = log.remove(layer_key);
+            if let Some(id) = log.remove(ecs_key).or(layer_id) {
+                insert_dotted(&mut ecs, ecs_key, id);
+            }
+        }
+
+        let error = LoggedError::find(&log).map(|error| {
+            let field = error.field.to_string();
+            let mut ecs_error = json!({ "message": error.message, "stack_trace": error.stack() });
+            if let Some(kind) = error.kind {
+                ecs_error["type"] = json!(kind);
+            }
+            (field, ecs_error)
+        });
+        if let Some((field, ecs_error)) = error {
+            log.remove(&field);
+            ecs.insert("error".to_string(), ecs_error);
+        }
+
+        // the leaf span is the last one of the list
+        let spans = log.remove("spans");
+        let leaf = log.remove("span");
+        if let Some(spans) = spans.or_else(|| leaf.map(|leaf| json!([leaf]))) {
+            log.insert("spans".to_string(), spans);
+        }
+
+        ecs.insert("ecs".to_string(), json!({ "version": ECS_VERSION }));
+
+        let mut document = match &self.namespace {
+            Some(namespace) if !log.is_empty() => {
+                let mut document = Map::new();
+                document.insert(namespace.clone(), Value::Object(log));
+                document
+            }
+            Some(_) => Map::new(),
+            None => log,
+        };
+        document.extend(ecs);
+        document
+    }
+}
+
+impl LogProcessor for EcsFormatter {
+    fn process(&self, log: &mut Log) -> Decision {
+        *log = self.format(std::mem::take(log));
+        Decision::Keep
+    }
+}
@@ -0,0 +1,175 @@
+use crate::options::ThreadFields;
+use crate::trace_parent::TraceParent;
+use crate::visitor::Fields;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tracing::{callsite::Identifier, Metadata};
+
+/// Fields of a span, stored in its extensions when the span is created.
+///
+/// They're shared with every event captured inside the span, so they're never copied.
+#[derive(Debug, Clone)]
+pub struct SpanFields(pub Arc<Fields>);
+
+/// Time spent by a span inside and outside its scope, stored in its extensions.
+#[derive(Debug, Clone, Copy)]
+pub struct SpanTimings {
+    pub created: Instant,
+    /// Last time the span was entered or exited.
+    pub last: Instant,
+    pub busy_ns: u64,
+    pub idle_ns: u64,
+}
+
+impl SpanTimings {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            created: now,
+            last: now,
+            busy_ns: 0,
+            idle_ns: 0,
+        }
+    }
+
+    /// Returns the nanoseconds elapsed since the last call and resets the clock.
+    pub fn lap(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_nanos();
+        self.last = now;
+        u64::try_from(elapsed).unwrap_or(u64::MAX)
+    }
+}
+
+/// Identity of a span, stored in its extensions when the span is created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanIds {
+    pub span_id: u64,
+    pub parent_id: Option<u64>,
+    /// Generated for root spans and inherited by their descendants.
+    pub trace_id: u128,
+    /// Spans this one follows from.
+    pub follows_from: Vec<u64>,
+    /// Remote parent received in a `traceparent` field of this span or of an ancestor.
+    pub remote: Option<TraceParent>,
+}
+
+/// Generates a random trace id.
+///
+/// Every `RandomState` is seeded differently, which is enough to tell traces apart.
+pub fn new_trace_id() -> u128 {
+    let random = || RandomState::new().build_hasher().finish();
+    (u128::from(random()) << 64) | u128::from(random())
+}
+
+/// Stable hash of a callsite, built from its location, level and field names.
+///
+/// Unlike the callsite identifier, it doesn't change between runs or builds,
+/// so it can be used to group the logs of the same statement. It's a 64-bit FNV-1a hash, hex-encoded.
+fn fingerprint(metadata: &Metadata<'_>) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let location = metadata.file().unwrap_or_else(|| metadata.target());
+    let line = metadata.line().unwrap_or_default();
+    let fields: Vec<&str> = metadata.fields().iter().map(|field| field.name()).collect();
+    let key = format!(
+        "{location}:{line}:{}:{}",
+        metadata.level(),
+        fields.join(",")
+    );
+    let hash = key.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    format!("{hash:016x}")
+}
+
+/// Fingerprints of the callsites seen so far, so that each one is only hashed once.
+#[derive(Debug, Default)]
+pub struct Fingerprints(HashMap<Identifier, String>);
+
+impl Fingerprints {
+    pub fn get(&mut self, metadata: &Metadata<'_>) -> &str {
+        self.0
+            .entry(metadata.callsite())
+            .or_insert_with(|| fingerprint(metadata))
+    }
+}
+
+/// Ids of the OpenTelemetry span, read from the `OtelData` of `tracing-opentelemetry`.
+#[cfg(feature = "opentelemetry")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtelIds {
+    pub trace_id: u128,
+    pub span_id: u64,
+}
+
+#[derive(Debug)]
+pub struct CapturedSpan {
+    pub name: &'static str,
+    pub fields: Option<SpanFields>,
+}
+
+/// Next id handed to a thread by [`THREAD_ID`].
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Process-local id of the current thread, assigned the first time it's read.
+    /// `std::thread::ThreadId` only exposes its number through its `Debug` output, which isn't stable.
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Thread and task that emitted an event, only captured when enabled in the options.
+#[derive(Debug, Default)]
+pub struct ThreadInfo {
+    pub name: Option<String>,
+    pub id: Option<u64>,
+    pub task_id: Option<tokio::task::Id>,
+}
+
+impl ThreadInfo {
+    pub fn capture(fields: ThreadFields) -> Self {
+        if !fields.any() {
+            return Self::default();
+        }
+        let thread = std::thread::current();
+        Self {
+            name: fields
+                .name
+                .then(|| thread.name().map(String::from))
+                .flatten(),
+            // the id is gone while the thread is being torn down
+            id: fields
+                .id
+                .then(|| THREAD_ID.try_with(|id| *id).ok())
+                .flatten(),
+            task_id: fields.task_id.then(tokio::task::try_id).flatten(),
+        }
+    }
+}
+
+/// Compact owned representation of an event, built on the thread that emitted it.
+///
+/// It's sent to the ingestion thread, which turns it into a [`crate::Log`].
+#[derive(Debug)]
+pub struct CapturedEvent {
+    pub metadata: &'static Metadata<'static>,
+    pub fields: Fields,
+    /// Spans containing the event, root first.
+    pub spans: Vec<CapturedSpan>,
+    /// Identity of the leaf span.
+    pub ids: Option<SpanIds>,
+    /// OpenTelemetry ids of the leaf span.
+    #[cfg(feature = "opentelemetry")]
+    pub otel: Option<OtelIds>,
+    pub thread: ThreadInfo,
+    pub timestamp: DateTime<Utc>,
+}
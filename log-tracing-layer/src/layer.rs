@@ -1,28 +1,130 @@
+use crate::batch_ingestor::{BatchLogIngestor, LegacyIngestor};
+use crate::event::{
+    new_trace_id, CapturedEvent, CapturedSpan, Fingerprints, SpanFields, SpanIds, SpanTimings,
+    ThreadInfo,
+};
 use crate::log_ingestor::Log;
 use crate::log_ingestor::LogIngestor;
-use crate::visitor::JsonVisitor;
+use crate::options::{FieldFilter, LogLayerOptions, SpanEvents, SpanFormat, TimestampFormat};
+use crate::processor::Decision;
+use crate::trace_parent::{TraceParent, TRACEPARENT_FIELD, TRACESTATE_FIELD};
+use crate::visitor::{FieldValue, Fields, JsonVisitor};
+use chrono::Utc;
 use serde_json::json;
 use serde_json::Map;
 use serde_json::Value;
-use tokio::sync::mpsc::unbounded_channel;
+use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
 use tracing::span;
 use tracing::Subscriber;
-use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
 use tracing_subscriber::Layer;
 
+/// Maximum amount of logs handed to the ingestor at once.
+const MAX_CHANNEL_BATCH: usize = 1024;
+
+/// Ingestion loop. It owns both the ingestor and its flush timer, so the periodic flushes
+/// can never race with the final one.
+async fn run<I: BatchLogIngestor>(
+    mut ingestor: I,
+    mut rx: UnboundedReceiver<CapturedEvent>,
+    options: Arc<LogLayerOptions>,
+) {
+    let apply_schema = !options.schema.is_empty() && !ingestor.accept_schema(&options.schema);
+    let mut handle = ingestor.start();
+    // the context never changes, so it's only detected once
+    let context = options.resolve_context();
+    let mut fingerprints = Fingerprints::default();
+    let mut timer = ingestor.flush_interval().map(|period| {
+        let mut timer = tokio::time::interval_at(Instant::now() + period, period);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        timer
+    });
+
+    let mut buffer = Vec::with_capacity(MAX_CHANNEL_BATCH);
+    loop {
+        tokio::select! {
+            // drain everything that is already in the channel at once
+            received = rx.recv_many(&mut buffer, MAX_CHANNEL_BATCH) => {
+                // the layer has been dropped
+                if received == 0 {
+                    break;
+                }
+                // draining keeps the capacity of the buffer for the next batch
+                #[allow(clippy::iter_with_drain)]
+                let logs: Vec<Log> = buffer
+                    .drain(..)
+                    .map(|e| create_log(e, &options, &context, &mut fingerprints))
+                    .filter_map(|mut log| {
+                        if options.processors.process(&mut log) == Decision::Drop {
+                            return None;
+                        }
+                        if apply_schema {
+                            options.schema.apply(&mut log);
+                        }
+                        Some(log)
+                    })
+                    .collect();
+                // every log may have been dropped by the processors
+                if !logs.is_empty() {
+                    ingestor.ingest_batch(logs).await;
+                }
+            },
+            () = tick(timer.as_mut()) => {
+                if let Err(e) = ingestor.flush().await {
+                    eprintln!("LAYER: Error flushing the ingestor: {e}");
+                }
+            }
+        }
+    }
+
+    handle.cancel();
+    if let Err(e) = ingestor.shutdown().await {
+        eprintln!("LAYER: Error shutting down the ingestor: {e}");
+    }
+}
+
+async fn tick(timer: Option<&mut Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub struct LogLayer {
-    tx: Option<tokio::sync::mpsc::UnboundedSender<Log>>,
+    tx: Option<tokio::sync::mpsc::UnboundedSender<CapturedEvent>>,
     handle: Option<std::thread::JoinHandle<()>>,
+    /// Shared with the ingestion thread.
+    options: Arc<LogLayerOptions>,
 }
 
 impl LogLayer {
-    pub fn new<I>(mut ingestor: I) -> Self
+    pub fn new<I>(ingestor: I) -> Self
     where
         I: LogIngestor + 'static,
     {
-        let (tx, mut rx) = unbounded_channel::<Log>();
+        Self::from_batch_ingestor(LegacyIngestor::new(ingestor))
+    }
+
+    pub fn from_batch_ingestor<I>(ingestor: I) -> Self
+    where
+        I: BatchLogIngestor + 'static,
+    {
+        Self::with_options(ingestor, LogLayerOptions::default())
+    }
+
+    pub fn with_options<I>(ingestor: I, options: LogLayerOptions) -> Self
+    where
+        I: BatchLogIngestor + 'static,
+    {
+        let options = Arc::new(options);
+        let (tx, rx) = unbounded_channel::<CapturedEvent>();
+        let worker_options = Arc::clone(&options);
         // create a separate thread to manage log ingestion
         let handle = std::thread::Builder::new()
             .name(ingestor.name().into())
@@ -38,13 +140,7 @@ impl LogLayer {
                     Ok(r) => r,
                 };
 
-                rt.block_on(async move {
-                    ingestor.start();
-                    while let Some(log) = rx.recv().await {
-                        ingestor.ingest(log).await;
-                    }
-                    ingestor.flush().await;
-                });
+                rt.block_on(run(ingestor, rx, worker_options));
                 drop(rt);
             })
             .expect("Something went wrong spawning the thread");
@@ -52,62 +148,408 @@ impl LogLayer {
         Self {
             tx: Some(tx),
             handle: Some(handle),
+            options,
         }
     }
+}
 
-    fn create_log<S: Subscriber + for<'a> LookupSpan<'a>>(
-        event: &tracing::Event<'_>,
-        ctx: &tracing_subscriber::layer::Context<'_, S>,
-    ) -> Map<String, Value> {
-        let mut log: Map<String, Value> = Map::new();
-        let mut spans: Vec<Map<String, Value>> = vec![];
-
-        if let Some(scope) = ctx.event_scope(event) {
-            for span in scope.from_root() {
-                let mut new_span: Map<String, Value> = Map::new();
-                new_span.insert("name".to_string(), json!(span.name()));
-                if let Some(fields) = span.extensions_mut().get_mut::<Map<String, Value>>() {
-                    new_span.append(fields);
-                }
-                spans.push(new_span);
+fn insert_fields(map: &mut Map<String, Value>, fields: &Fields, options: &LogLayerOptions) {
+    for (name, value) in fields {
+        let (name, parse_json) = options.json_parsing.resolve(name);
+        let value = value.to_json(parse_json, options);
+        if options.expand_dotted_keys {
+            insert_dotted(map, name, value);
+        } else {
+            map.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// Inserts `value` into nested objects following the segments of `name`.
+///
+/// Falls back to the full dotted name when the path conflicts with existing values.
+/// A name without dots takes the place of the object created by previous dotted names,
+/// whose entries are moved back to their full dotted names.
+pub fn insert_dotted(map: &mut Map<String, Value>, name: &str, value: Value) {
+    let segments: Vec<&str> = name.split('.').collect();
+    let Some((last, parents)) = segments.split_last() else {
+        return;
+    };
+    if parents.is_empty() || segments.iter().any(|s| s.is_empty()) {
+        if let Some(Value::Object(object)) = map.insert(name.to_string(), value) {
+            for (key, value) in object {
+                map.insert(format!("{name}.{key}"), value);
+            }
+        }
+        return;
+    }
+
+    // check the whole path before creating any object
+    let mut current = Some(&*map);
+    for segment in parents {
+        match current.and_then(|object| object.get(*segment)) {
+            Some(Value::Object(object)) => current = Some(object),
+            Some(_) => {
+                map.insert(name.to_string(), value);
+                return;
             }
+            None => current = None,
         }
+    }
+    if current.is_some_and(|object| object.get(*last).is_some_and(Value::is_object)) {
+        map.insert(name.to_string(), value);
+        return;
+    }
+
+    let mut current = map;
+    for segment in parents {
+        current = match current
+            .entry(*segment)
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(object) => object,
+            _ => unreachable!("the path has been checked"),
+        };
+    }
+    current.insert((*last).to_string(), value);
+}
 
-        // if no last span, it means there are no spans at all
-        if let Some(last) = spans.last() {
-            log.insert("span".to_string(), json!(last));
-            log.insert("spans".to_string(), json!(spans));
+/// Adds the entries of `source` that are missing from `target`, merging the objects found in both.
+fn merge_missing(target: &mut Map<String, Value>, source: Map<String, Value>) {
+    for (key, value) in source {
+        match (target.get_mut(&key), value) {
+            (None, value) => {
+                target.insert(key, value);
+            }
+            (Some(Value::Object(target)), Value::Object(source)) => merge_missing(target, source),
+            (Some(_), _) => {}
         }
+    }
+}
 
+fn span_to_json(span: &CapturedSpan, options: &LogLayerOptions) -> Value {
+    let mut map: Map<String, Value> = Map::new();
+    map.insert("name".to_string(), json!(span.name));
+    if let Some(SpanFields(fields)) = &span.fields {
+        insert_fields(&mut map, fields, options);
+    }
+    Value::Object(map)
+}
+
+/// Inserts the ids as lowercase hexadecimal strings, like W3C trace context ones.
+fn insert_ids(log: &mut Map<String, Value>, ids: &SpanIds) {
+    log.insert(
+        "trace_id".to_string(),
+        json!(format!("{:032x}", ids.trace_id)),
+    );
+    log.insert(
+        "span_id".to_string(),
+        json!(format!("{:016x}", ids.span_id)),
+    );
+    if let Some(parent_id) = ids.parent_id {
+        log.insert("parent_id".to_string(), json!(format!("{parent_id:016x}")));
+    }
+    if !ids.follows_from.is_empty() {
+        let follows_from: Vec<String> = ids
+            .follows_from
+            .iter()
+            .map(|id| format!("{id:016x}"))
+            .collect();
+        log.insert("follows_from".to_string(), json!(follows_from));
+    }
+    if let Some(remote) = &ids.remote {
+        let parent_id = format!("{:016x}", remote.parent_id);
+        log.insert("parent_span_id".to_string(), json!(parent_id));
+        // same correlation attributes as the OpenTelemetry ones
         log.insert(
-            "level".to_string(),
-            json!(event.metadata().level().as_str()),
+            "trace.id".to_string(),
+            json!(format!("{:032x}", remote.trace_id)),
         );
-        log.insert("target".to_string(), json!(event.metadata().target()));
+        log.insert("span.id".to_string(), json!(parent_id));
+        if let Some(tracestate) = &remote.tracestate {
+            log.insert("tracestate".to_string(), json!(tracestate));
+        }
+    }
+}
+
+/// Reads the `traceparent` and `tracestate` fields of a span, ignoring invalid values.
+fn remote_parent(fields: &Fields) -> Option<TraceParent> {
+    let field = |name: &str| {
+        fields.iter().find_map(|(field, value)| match value {
+            FieldValue::Str(value) | FieldValue::Debug(value) if *field == name => {
+                Some(value.trim_matches('"'))
+            }
+            _ => None,
+        })
+    };
+    let parent = TraceParent::parse(field(TRACEPARENT_FIELD)?).ok()?;
+    Some(match field(TRACESTATE_FIELD) {
+        Some(tracestate) => parent.with_tracestate(tracestate),
+        None => parent,
+    })
+}
 
-        if let Some(file) = event.metadata().file() {
-            log.insert("file".to_string(), json!(file));
+/// Keys added by the layer itself, which are subject to the [`crate::FieldFilter`] too,
+/// along with the timestamp key.
+pub const METADATA_KEYS: &[&str] = &[
+    "span_path",
+    "span",
+    "spans",
+    "level",
+    "target",
+    "file",
+    "line",
+    "module_path",
+    "event.name",
+    "fingerprint",
+    "trace_id",
+    "span_id",
+    "parent_id",
+    "follows_from",
+    "parent_span_id",
+    "trace.id",
+    "span.id",
+    "tracestate",
+    "thread.name",
+    "thread.id",
+    "task.id",
+];
+
+/// Adds the thread and task of the event, e.g. `thread.id`.
+/// They're never nested, like `trace.id`, so that they can be filtered and mapped by their key.
+fn insert_thread(log: &mut Log, thread: ThreadInfo) {
+    // the `Display` output of a task id is its number
+    let task_id = thread
+        .task_id
+        .and_then(|id| id.to_string().parse::<u64>().ok());
+    let entries = [
+        ("thread.name", thread.name.map(Value::from)),
+        ("thread.id", thread.id.map(Value::from)),
+        ("task.id", task_id.map(Value::from)),
+    ];
+    for (key, value) in entries {
+        if let Some(value) = value {
+            log.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Turns a captured event into a [`Log`]. This runs in the ingestion thread.
+fn create_log(
+    event: CapturedEvent,
+    options: &LogLayerOptions,
+    context: &Map<String, Value>,
+    fingerprints: &mut Fingerprints,
+) -> Log {
+    let mut log: Map<String, Value> = Map::new();
+    let mut flattened: Map<String, Value> = Map::new();
+
+    if options.span_path && !event.spans.is_empty() {
+        let path: Vec<&str> = event.spans.iter().map(|span| span.name).collect();
+        log.insert("span_path".to_string(), json!(path.join(">")));
+    }
+
+    match options.span_format {
+        SpanFormat::None => {}
+        SpanFormat::Flattened => {
+            // root first, so the leaf-most span wins
+            for span in &event.spans {
+                if let Some(SpanFields(fields)) = &span.fields {
+                    insert_fields(&mut flattened, fields, options);
+                }
+            }
         }
-        if let Some(line) = event.metadata().line() {
-            log.insert("line".to_string(), json!(line));
+        SpanFormat::Leaf => {
+            if let Some(last) = event.spans.last() {
+                log.insert("span".to_string(), span_to_json(last, options));
+            }
         }
+        SpanFormat::List | SpanFormat::LeafAndList => {
+            let spans: Vec<Value> = event
+                .spans
+                .iter()
+                .map(|span| span_to_json(span, options))
+                .collect();
 
-        let mut visitor = JsonVisitor::default();
-        event.record(&mut visitor);
+            // if no last span, it means there are no spans at all
+            if let Some(last) = spans.last() {
+                if options.span_format == SpanFormat::LeafAndList {
+                    log.insert("span".to_string(), last.clone());
+                }
+                log.insert("spans".to_string(), Value::Array(spans));
+            }
+        }
+    }
 
-        visitor.fields.iter().for_each(|(k, v)| {
-            log.insert(k.clone(), v.clone());
-        });
+    let metadata = event.metadata;
+    log.insert("level".to_string(), json!(metadata.level().as_str()));
+    log.insert("target".to_string(), json!(metadata.target()));
+
+    if let Some(file) = metadata.file() {
+        log.insert("file".to_string(), json!(file));
+    }
+    if let Some(line) = metadata.line() {
+        log.insert("line".to_string(), json!(line));
+    }
+    if options.callsite_fields.module_path {
+        if let Some(module_path) = metadata.module_path() {
+            log.insert("module_path".to_string(), json!(module_path));
+        }
+    }
+    if options.callsite_fields.event_name {
+        log.insert("event.name".to_string(), json!(metadata.name()));
+    }
+    if options.callsite_fields.fingerprint {
+        log.insert("fingerprint".to_string(), json!(fingerprints.get(metadata)));
+    }
+
+    insert_thread(&mut log, event.thread);
 
+    if let Some(ids) = event.ids {
+        insert_ids(&mut log, &ids);
+    }
+    // OpenTelemetry and New Relic correlation attributes
+    #[cfg(feature = "opentelemetry")]
+    if let Some(otel) = event.otel {
+        log.insert(
+            "trace.id".to_string(),
+            json!(format!("{:032x}", otel.trace_id)),
+        );
         log.insert(
-            "timestamp".to_string(),
-            json!(chrono::Utc::now().to_rfc3339()),
+            "span.id".to_string(),
+            json!(format!("{:016x}", otel.span_id)),
         );
+    }
+
+    insert_fields(&mut log, &event.fields, options);
+
+    let timestamp = match &options.timestamp_format {
+        Some(format) => format.format(event.timestamp),
+        None => TimestampFormat::default().format(event.timestamp),
+    };
+    log.insert(options.timestamp_key().to_string(), timestamp);
+
+    // span fields only fill the gaps left by the event, then the context fills the rest
+    merge_missing(&mut log, flattened);
+    for (key, value) in context {
+        log.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+
+    // fields have already been filtered when captured
+    if options.field_filter != FieldFilter::All {
+        for key in METADATA_KEYS
+            .iter()
+            .copied()
+            .chain([options.timestamp_key()])
+        {
+            if !options.field_filter.allows(key) {
+                log.remove(key);
+            }
+        }
+    }
+
+    log
+}
+
+impl LogLayer {
+    /// Captures the event without serializing anything, to keep the emitting thread cheap.
+    fn capture_event<S: Subscriber + for<'a> LookupSpan<'a>>(
+        &self,
+        event: &tracing::Event<'_>,
+        ctx: &tracing_subscriber::layer::Context<'_, S>,
+    ) -> CapturedEvent {
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| scope.from_root().map(|span| capture_span(&span)).collect())
+            .unwrap_or_default();
+        let leaf = ctx.event_span(event);
+
+        let mut visitor = JsonVisitor::new(&self.options);
+        event.record(&mut visitor);
+
+        CapturedEvent {
+            metadata: event.metadata(),
+            fields: visitor.fields,
+            spans,
+            ids: leaf.as_ref().and_then(span_ids),
+            #[cfg(feature = "opentelemetry")]
+            otel: leaf.as_ref().and_then(otel_ids),
+            thread: ThreadInfo::capture(self.options.thread_fields),
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Captures a synthetic event for a span lifecycle event, with the span as the leaf one.
+    fn capture_span_event<S: Subscriber + for<'a> LookupSpan<'a>>(
+        &self,
+        span: &SpanRef<'_, S>,
+        message: &'static str,
+        timings: Option<&SpanTimings>,
+    ) -> CapturedEvent {
+        let mut fields: Fields = vec![("message", FieldValue::Str(message.to_string()))];
+        if let Some(timings) = timings {
+            let duration_ns = timings.last.duration_since(timings.created).as_nanos();
+            let duration_ns = u64::try_from(duration_ns).unwrap_or(u64::MAX);
+            fields.push(("duration_ns", FieldValue::U64(duration_ns)));
+            fields.push(("busy_ns", FieldValue::U64(timings.busy_ns)));
+            fields.push(("idle_ns", FieldValue::U64(timings.idle_ns)));
+        }
+        // like the fields of a real event
+        fields.retain(|(name, _)| self.options.field_filter.allows(name));
+
+        CapturedEvent {
+            metadata: span.metadata(),
+            fields,
+            spans: span
+                .scope()
+                .from_root()
+                .map(|span| capture_span(&span))
+                .collect(),
+            ids: span_ids(span),
+            #[cfg(feature = "opentelemetry")]
+            otel: otel_ids(span),
+            thread: ThreadInfo::capture(self.options.thread_fields),
+            timestamp: Utc::now(),
+        }
+    }
 
-        log
+    fn send(&self, event: CapturedEvent) {
+        if let Some(tx) = &self.tx {
+            if let Err(e) = tx.send(event) {
+                eprintln!("LAYER: Error sending log to ingestor: {e:?}");
+            }
+        }
+    }
+
+    fn span_events(&self, events: SpanEvents) -> bool {
+        self.options.span_events.contains(events)
     }
 }
 
+fn capture_span<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>) -> CapturedSpan {
+    CapturedSpan {
+        name: span.name(),
+        fields: span.extensions().get::<SpanFields>().cloned(),
+    }
+}
+
+fn span_ids<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>) -> Option<SpanIds> {
+    span.extensions().get::<SpanIds>().cloned()
+}
+
+/// Reads the ids of the OpenTelemetry span, which are known once the span has been entered.
+#[cfg(feature = "opentelemetry")]
+fn otel_ids<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>) -> Option<crate::event::OtelIds> {
+    let (trace_id, span_id) = span
+        .extensions()
+        .get::<tracing_opentelemetry::OtelData>()
+        .and_then(|data| Some((data.trace_id()?, data.span_id()?)))?;
+    (trace_id != opentelemetry::TraceId::INVALID).then(|| crate::event::OtelIds {
+        trace_id: u128::from_be_bytes(trace_id.to_bytes()),
+        span_id: u64::from_be_bytes(span_id.to_bytes()),
+    })
+}
+
 impl Drop for LogLayer {
     fn drop(&mut self) {
         // closing the channel
@@ -133,20 +575,92 @@ where
     ) {
         let span = ctx.span(id).expect("Span not found, this is a bug");
         let mut extensions = span.extensions_mut();
-        // visit values and insert them into extensions as serde_json::Map<String, serde_json::Value>
-        // this way, we will be able to access them later
-        let mut visitor = JsonVisitor::default();
+        // visit values and insert them into extensions as SpanFields
+        // this way, we will be able to share them with the events of the span
+        let mut visitor = JsonVisitor::new(&self.options);
         attrs.record(&mut visitor);
-        extensions.insert(visitor.fields);
+        let parent = span.parent();
+        let parent_ids = parent
+            .as_ref()
+            .and_then(|parent| parent.extensions().get::<SpanIds>().cloned());
+        // a traceparent field starts a new trace, continuing the remote one
+        let remote = remote_parent(&visitor.fields)
+            .or_else(|| parent_ids.as_ref().and_then(|ids| ids.remote.clone()));
+        let trace_id = match (&remote, &parent_ids) {
+            (Some(remote), _) => remote.trace_id,
+            (None, Some(ids)) => ids.trace_id,
+            (None, None) => new_trace_id(),
+        };
+        extensions.insert(SpanFields(Arc::new(visitor.fields)));
+        extensions.insert(SpanIds {
+            span_id: id.into_u64(),
+            parent_id: parent.map(|parent| parent.id().into_u64()),
+            trace_id,
+            follows_from: vec![],
+            remote,
+        });
+        if self.span_events(SpanEvents::CLOSE) {
+            extensions.insert(SpanTimings::new());
+        }
+        drop(extensions);
+
+        if self.span_events(SpanEvents::NEW) {
+            self.send(self.capture_span_event(&span, "new", None));
+        }
     }
 
     fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
         // send to the channel
-        if let Some(tx) = &self.tx {
-            let log = Self::create_log(event, &ctx);
-            if let Err(e) = tx.send(log) {
-                eprintln!("LAYER: Error sending log to ingestor: {e:?}");
-            }
+        if self.tx.is_some() {
+            self.send(self.capture_event(event, &ctx));
+        }
+    }
+
+    fn on_follows_from(
+        &self,
+        id: &span::Id,
+        follows: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else { return };
+        if let Some(ids) = span.extensions_mut().get_mut::<SpanIds>() {
+            ids.follows_from.push(follows.into_u64());
+        };
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        if let Some(timings) = span.extensions_mut().get_mut::<SpanTimings>() {
+            timings.idle_ns = timings.idle_ns.saturating_add(timings.lap());
+        }
+        if self.span_events(SpanEvents::ENTER) {
+            self.send(self.capture_span_event(&span, "enter", None));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        if let Some(timings) = span.extensions_mut().get_mut::<SpanTimings>() {
+            timings.busy_ns = timings.busy_ns.saturating_add(timings.lap());
+        }
+        if self.span_events(SpanEvents::EXIT) {
+            self.send(self.capture_span_event(&span, "exit", None));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if !self.span_events(SpanEvents::CLOSE) {
+            return;
         }
+        let Some(span) = ctx.span(&id) else { return };
+        let timings = span
+            .extensions_mut()
+            .get_mut::<SpanTimings>()
+            .map(|timings| {
+                timings.idle_ns = timings.idle_ns.saturating_add(timings.lap());
+                *timings
+            });
+        let event = self.capture_span_event(&span, "close", timings.as_ref());
+        self.send(event);
     }
 }
@@ -8,10 +8,45 @@
 //!
 //! ## How to use it
 //!
-//! Feel free to look at the [dd-tracing-layer](https://docs.rs/dd-tracing-layer) crate to see how to use this crate, but basically, you need to provide a [`LogIngestor`] implementation.
+//! Feel free to look at the [dd-tracing-layer](https://docs.rs/dd-tracing-layer) crate to see how to use this crate, but basically, you need to provide a [`BatchLogIngestor`] implementation.
+//!
+//! Implementations of the original [`LogIngestor`] trait keep working through the [`LegacyIngestor`] adapter, which [`LogLayer::new`] applies for you.
+mod batch_ingestor;
+mod context;
+mod ecs;
+mod event;
 mod layer;
 mod log_ingestor;
+mod log_record;
+mod logged_error;
+mod options;
+mod processor;
+#[cfg(feature = "redaction")]
+mod redaction;
+mod routing;
+mod schema;
+mod trace_parent;
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+mod valuable;
 mod visitor;
 
+pub use batch_ingestor::{
+    BatchLogIngestor, FlushReport, IngestError, IngestorHandle, LegacyIngestor,
+};
+pub use chrono::SecondsFormat;
+pub use context::ContextProvider;
+pub use ecs::{EcsFormatter, ECS_VERSION};
 pub use layer::LogLayer;
 pub use log_ingestor::{Log, LogEvent, LogIngestor};
+pub use log_record::{LogRecord, LogRecordFormatter};
+pub use logged_error::LoggedError;
+pub use options::{
+    BytesEncoding, CallsiteFields, FieldFilter, JsonParsing, LargeIntegers, LogLayerOptions,
+    SpanEvents, SpanFormat, ThreadFields, TimestampFormat, ValuableLimits,
+};
+pub use processor::{Decision, LogProcessor, ProcessorChain};
+#[cfg(feature = "redaction")]
+pub use redaction::{Pattern, RedactAction, Redactor};
+pub use routing::{RouteFilter, RouteFilterError, Router};
+pub use schema::Schema;
+pub use trace_parent::{TraceParent, TraceParentError, TRACEPARENT_FIELD, TRACESTATE_FIELD};
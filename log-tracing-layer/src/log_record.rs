@@ -0,0 +1,269 @@
+use crate::context::ContextProvider;
+use crate::log_ingestor::Log;
+use crate::logged_error::LoggedError;
+use crate::options::{LogLayerOptions, TimestampFormat};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+
+/// Keys of the layer output mapped to OpenTelemetry attributes.
+const ATTRIBUTES: &[(&str, &str)] = &[
+    ("target", "code.namespace"),
+    ("file", "code.filepath"),
+    ("line", "code.lineno"),
+];
+
+/// Context keys mapped to OpenTelemetry resource attributes.
+const RESOURCE: &[(&str, &str)] = &[
+    ("host", "host.name"),
+    ("pid", "process.pid"),
+    ("executable", "process.executable.name"),
+    ("service", "service.name"),
+    ("version", "service.version"),
+    ("env", "deployment.environment.name"),
+];
+
+/// A log following the [OpenTelemetry log data model](https://opentelemetry.io/docs/specs/otel/logs/data-model/).
+///
+/// Built from the logs of the layer with a [`LogRecordFormatter`], and encoded for OTLP
+/// with [`LogRecord::to_otlp_json`] or [`LogRecord::export_request`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogRecord {
+    pub time_unix_nano: u64,
+    pub observed_time_unix_nano: u64,
+    pub severity_number: u8,
+    pub severity_text: String,
+    pub body: Value,
+    /// Flat attributes, whose keys may contain dots.
+    pub attributes: Map<String, Value>,
+    pub resource: Map<String, Value>,
+    /// Hex-encoded, 32 characters.
+    pub trace_id: Option<String>,
+    /// Hex-encoded, 16 characters.
+    pub span_id: Option<String>,
+}
+
+impl LogRecord {
+    /// Maps a `tracing` level to the OpenTelemetry `SeverityNumber`.
+    #[must_use]
+    pub const fn severity_number(level: tracing::Level) -> u8 {
+        match level {
+            tracing::Level::TRACE => 1,
+            tracing::Level::DEBUG => 5,
+            tracing::Level::INFO => 9,
+            tracing::Level::WARN => 13,
+            tracing::Level::ERROR => 17,
+        }
+    }
+
+    /// Encodes the record as an OTLP/JSON `LogRecord`.
+    #[must_use]
+    pub fn to_otlp_json(&self) -> Value {
+        let mut record = json!({
+            "timeUnixNano": self.time_unix_nano.to_string(),
+            "observedTimeUnixNano": self.observed_time_unix_nano.to_string(),
+            "severityNumber": self.severity_number,
+            "severityText": self.severity_text,
+            "body": any_value(&self.body),
+            "attributes": key_values(&self.attributes),
+        });
+        if let Some(trace_id) = &self.trace_id {
+            record["traceId"] = json!(trace_id);
+        }
+        if let Some(span_id) = &self.span_id {
+            record["spanId"] = json!(span_id);
+        }
+        record
+    }
+
+    /// Encodes the records as the body of an OTLP/JSON `ExportLogsServiceRequest`,
+    /// grouping them by resource.
+    #[must_use]
+    pub fn export_request(records: &[Self]) -> Value {
+        let mut groups: Vec<(&Map<String, Value>, Vec<Value>)> = vec![];
+        for record in records {
+            match groups.iter_mut().find(|(res, _)| *res == &record.resource) {
+                Some((_, logs)) => logs.push(record.to_otlp_json()),
+                None => groups.push((&record.resource, vec![record.to_otlp_json()])),
+            }
+        }
+        let resource_logs: Vec<Value> = groups
+            .into_iter()
+            .map(|(resource, logs)| {
+                json!({
+                    "resource": { "attributes": key_values(resource) },
+                    "scopeLogs": [{
+                        "scope": {
+                            "name": env!("CARGO_PKG_NAME"),
+                            "version": env!("CARGO_PKG_VERSION"),
+                        },
+                        "logRecords": logs,
+                    }],
+                })
+            })
+            .collect();
+        json!({ "resourceLogs": resource_logs })
+    }
+}
+
+fn key_values(map: &Map<String, Value>) -> Vec<Value> {
+    map.iter()
+        .map(|(key, value)| json!({ "key": key, "value": any_value(value) }))
+        .collect()
+}
+
+/// Encodes a value as an OTLP `AnyValue`. 64-bit integers are encoded as strings.
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({}),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => json!({ "intValue": i.to_string() }),
+            // integers over i64::MAX don't fit in an intValue
+            (None, _) if n.is_u64() => json!({ "stringValue": n.to_string() }),
+            (None, f) => json!({ "doubleValue": f }),
+        },
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Array(values) => {
+            json!({ "arrayValue": { "values": values.iter().map(any_value).collect::<Vec<_>>() } })
+        }
+        Value::Object(map) => json!({ "kvlistValue": { "values": key_values(map) } }),
+    }
+}
+
+/// Converts the logs of the layer into OpenTelemetry [`LogRecord`]s.
+///
+/// `message` becomes the body, the resource keys (usually the context of the layer)
+/// become the resource, and every other field an attribute.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogRecordFormatter {
+    resource_keys: Vec<String>,
+    timestamp_format: TimestampFormat,
+    timestamp_key: Option<String>,
+}
+
+impl LogRecordFormatter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a formatter using the static context and the context providers of the options as the resource,
+    /// and reading the timestamp the way the options write it.
+    #[must_use]
+    pub fn from_options(options: &LogLayerOptions) -> Self {
+        let formatter = options
+            .context_providers
+            .iter()
+            .map(ContextProvider::key)
+            .fold(Self::new(), Self::with_resource_key);
+        let mut formatter = options
+            .context
+            .keys()
+            .fold(formatter, Self::with_resource_key);
+        formatter.timestamp_format = options.timestamp_format.clone().unwrap_or_default();
+        formatter.timestamp_key.clone_from(&options.timestamp_key);
+        formatter
+    }
+
+    /// Format in which the timestamp of the logs was written, RFC3339 by default.
+    #[must_use]
+    pub fn with_timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Key of the timestamp of the logs, `timestamp` by default.
+    #[must_use]
+    pub fn with_timestamp_key(mut self, timestamp_key: impl Into<String>) -> Self {
+        self.timestamp_key = Some(timestamp_key.into());
+        self
+    }
+
+    /// Moves the given key of the logs to the resource.
+    #[must_use]
+    pub fn with_resource_key(mut self, key: impl Into<String>) -> Self {
+        let key = key.into();
+        if !self.resource_keys.contains(&key) {
+            self.resource_keys.push(key);
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn format(&self, mut log: Log) -> LogRecord {
+        let observed_time_unix_nano = unix_nanos(Utc::now());
+        let time_unix_nano = log
+            .remove(self.timestamp_key.as_deref().unwrap_or("timestamp"))
+            .and_then(|timestamp| self.timestamp_format.parse(&timestamp))
+            .map_or(observed_time_unix_nano, unix_nanos);
+
+        let severity_text = match log.remove("level") {
+            Some(Value::String(level)) => level,
+            _ => String::new(),
+        };
+        let severity_number = severity_text.parse().map_or(0, LogRecord::severity_number);
+
+        let mut resource = Map::new();
+        for key in &self.resource_keys {
+            if let Some(value) = log.remove(key) {
+                let name = RESOURCE
+                    .iter()
+                    .find(|(from, _)| from == key)
+                    .map_or(key.as_str(), |(_, to)| to);
+                resource.insert(name.to_string(), value);
+            }
+        }
+
+        // OpenTelemetry or remote ids first, then the ones of the layer
+        let trace_id = log.remove("trace_id");
+        let trace_id = log.remove("trace.id").or(trace_id);
+        let span_id = log.remove("span_id");
+        let span_id = log.remove("span.id").or(span_id);
+
+        let mut mapped = Map::new();
+        let error = LoggedError::find(&log).map(|error| {
+            let mut exception = vec![
+                ("exception.message", json!(error.message)),
+                ("exception.stacktrace", json!(error.stack())),
+            ];
+            if let Some(kind) = error.kind {
+                exception.push(("exception.type", json!(kind)));
+            }
+            (error.field.to_string(), exception)
+        });
+        if let Some((field, exception)) = error {
+            log.remove(&field);
+            for (key, value) in exception {
+                mapped.insert(key.to_string(), value);
+            }
+        }
+        for (key, attribute) in ATTRIBUTES {
+            if let Some(value) = log.remove(*key) {
+                mapped.insert(attribute.to_string(), value);
+            }
+        }
+        let body = log.remove("message").unwrap_or_default();
+        // mapped attributes take precedence over fields with the same name
+        let mut attributes = log;
+        attributes.extend(mapped);
+
+        LogRecord {
+            time_unix_nano,
+            observed_time_unix_nano,
+            severity_number,
+            severity_text,
+            body,
+            attributes,
+            resource,
+            trace_id: trace_id.and_then(|id| id.as_str().map(String::from)),
+            span_id: span_id.and_then(|id| id.as_str().map(String::from)),
+        }
+    }
+}
+
+fn unix_nanos(timestamp: DateTime<Utc>) -> u64 {
+    timestamp
+        .timestamp_nanos_opt()
+        .and_then(|nanos| u64::try_from(nanos).ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,51 @@
+use crate::log_ingestor::Log;
+use serde_json::{Map, Value};
+
+/// An error recorded as a field of a [`Log`].
+///
+/// Errors are recorded as `{ "message": .., "kind": .., "chain": [..] }`, where `chain` holds
+/// the messages of the error sources and `kind` the type name of the error, when available.
+/// Ingestors can use it to fill the error attributes of their vendor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedError<'a> {
+    pub field: &'a str,
+    pub message: &'a str,
+    pub kind: Option<&'a str>,
+    pub chain: Vec<&'a str>,
+}
+
+impl<'a> LoggedError<'a> {
+    /// Finds the error recorded in the log, preferring the one in the `error` field.
+    #[must_use]
+    pub fn find(log: &'a Log) -> Option<Self> {
+        log.get_key_value("error")
+            .and_then(|(k, v)| Self::parse(k, v))
+            .or_else(|| log.iter().find_map(|(k, v)| Self::parse(k, v)))
+    }
+
+    fn parse(field: &'a str, value: &'a Value) -> Option<Self> {
+        let error: &Map<String, Value> = value.as_object()?;
+        let message = error.get("message")?.as_str()?;
+        let chain = error
+            .get("chain")?
+            .as_array()?
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        Some(Self {
+            field,
+            message,
+            kind: error.get("kind").and_then(Value::as_str),
+            chain,
+        })
+    }
+
+    /// The message of the error followed by the messages of its sources, one per line.
+    #[must_use]
+    pub fn stack(&self) -> String {
+        std::iter::once(self.message.to_string())
+            .chain(self.chain.iter().map(|cause| format!("Caused by: {cause}")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
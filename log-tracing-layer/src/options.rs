@@ -0,0 +1,515 @@
+use crate::context::ContextProvider;
+use crate::processor::{LogProcessor, ProcessorChain};
+use crate::schema::Schema;
+use base64::Engine;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use std::fmt::Write;
+
+/// Controls which string fields are parsed as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum JsonParsing {
+    /// String fields are always kept as strings.
+    #[default]
+    Never,
+    /// Only fields whose name ends with the given suffix are parsed.
+    /// The suffix is removed from the name, so `payload.json` becomes `payload`.
+    WithSuffix(String),
+    /// Every string field holding valid JSON is parsed.
+    /// Beware that values like `"123"`, `"true"` or `"null"` will lose their string type.
+    Always,
+}
+
+impl JsonParsing {
+    /// Returns the name of the field and whether its value must be parsed.
+    pub(crate) fn resolve<'a>(&self, name: &'a str) -> (&'a str, bool) {
+        match self {
+            Self::Never => (name, false),
+            Self::Always => (name, true),
+            Self::WithSuffix(suffix) => match name.strip_suffix(suffix.as_str()) {
+                Some(stripped) if !stripped.is_empty() => (stripped, true),
+                _ => (name, false),
+            },
+        }
+    }
+}
+
+/// Largest integer that can be represented exactly by a double precision float,
+/// which is what most JSON parsers use for numbers.
+const MAX_SAFE_INTEGER: i128 = (1 << 53) - 1;
+
+/// Controls how `i128` and `u128` fields are serialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LargeIntegers {
+    /// Numbers between -(2^53 - 1) and 2^53 - 1 are kept as numbers, any other is turned into a string.
+    #[default]
+    SafeNumbers,
+    /// Numbers fitting in 64 bits are kept as numbers, any other is turned into a string.
+    Numbers,
+    /// Numbers are always turned into strings.
+    Strings,
+}
+
+impl LargeIntegers {
+    pub(crate) fn format<T>(self, value: T) -> Value
+    where
+        T: Copy + ToString + TryInto<i64> + TryInto<u64> + TryInto<i128>,
+    {
+        let as_number = || {
+            TryInto::<i64>::try_into(value)
+                .map(Value::from)
+                .or_else(|_| TryInto::<u64>::try_into(value).map(Value::from))
+                .ok()
+        };
+        let number = match self {
+            Self::SafeNumbers => TryInto::<i128>::try_into(value)
+                .is_ok_and(|v| v.unsigned_abs() <= MAX_SAFE_INTEGER.unsigned_abs())
+                .then(as_number)
+                .flatten(),
+            Self::Numbers => as_number(),
+            Self::Strings => None,
+        };
+        number.unwrap_or_else(|| Value::String(value.to_string()))
+    }
+}
+
+/// Controls how byte slice fields are serialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BytesEncoding {
+    /// Lowercase hexadecimal string.
+    #[default]
+    Hex,
+    /// Standard base64 string, with padding.
+    Base64,
+    /// Array of numbers.
+    Array,
+}
+
+impl BytesEncoding {
+    pub(crate) fn encode(self, bytes: &[u8]) -> Value {
+        match self {
+            Self::Hex => Value::String(bytes.iter().fold(
+                String::with_capacity(bytes.len() * 2),
+                |mut hex, byte| {
+                    // writing to a `String` never fails
+                    let _ = write!(hex, "{byte:02x}");
+                    hex
+                },
+            )),
+            Self::Base64 => Value::String(base64::engine::general_purpose::STANDARD.encode(bytes)),
+            Self::Array => Value::from(bytes),
+        }
+    }
+}
+
+/// Controls how the spans containing an event are represented in its log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpanFormat {
+    /// Spans are omitted.
+    None,
+    /// Only the leaf span is kept, under `span`.
+    Leaf,
+    /// Only the whole stack is kept, root first, under `spans`.
+    List,
+    /// Both `span` and `spans` are kept.
+    #[default]
+    LeafAndList,
+    /// The fields of every span are merged into the top level of the log, the leaf-most span winning.
+    /// Event fields and metadata take precedence over span fields, even when dotted keys are expanded:
+    /// span fields are only merged into the objects of the event, never in place of its values.
+    Flattened,
+}
+
+/// Span lifecycle events that produce a log, similar to `tracing_subscriber::fmt::format::FmtSpan`.
+///
+/// Flags can be combined with `|`. Those logs have the `new`, `enter`, `exit` or `close` message,
+/// and the `close` ones also carry the `duration_ns`, `busy_ns` and `idle_ns` timings of the span.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpanEvents(u8);
+
+impl SpanEvents {
+    pub const NONE: Self = Self(0);
+    pub const NEW: Self = Self(1);
+    pub const ENTER: Self = Self(1 << 1);
+    pub const EXIT: Self = Self(1 << 2);
+    pub const CLOSE: Self = Self(1 << 3);
+    pub const ACTIVE: Self = Self(Self::ENTER.0 | Self::EXIT.0);
+    pub const FULL: Self = Self(Self::NEW.0 | Self::ACTIVE.0 | Self::CLOSE.0);
+
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl std::ops::BitOr for SpanEvents {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Selects the fields kept in the logs, by name or by prefix when the pattern ends with `*`.
+///
+/// It applies to event fields, span fields and metadata keys like `level`, `target` or `spans`.
+/// Event and span fields are filtered before they're even captured.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FieldFilter {
+    #[default]
+    All,
+    /// Only the matching fields are kept. Don't forget `message`, `level` and any other key you need.
+    Allow(Vec<String>),
+    /// The matching fields are dropped.
+    Deny(Vec<String>),
+}
+
+impl FieldFilter {
+    pub fn allow<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        Self::Allow(patterns.into_iter().map(Into::into).collect())
+    }
+
+    pub fn deny<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        Self::Deny(patterns.into_iter().map(Into::into).collect())
+    }
+
+    #[must_use]
+    pub fn allows(&self, name: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns.iter().any(|pattern| {
+                pattern
+                    .strip_suffix('*')
+                    .map_or_else(|| name == pattern, |prefix| name.starts_with(prefix))
+            })
+        };
+        match self {
+            Self::All => true,
+            Self::Allow(patterns) => matches(patterns),
+            Self::Deny(patterns) => !matches(patterns),
+        }
+    }
+}
+
+/// Controls how the timestamp of the logs is serialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC3339 string with the given fractional digits, using `Z` instead of `+00:00` if `use_z` is set.
+    Rfc3339 {
+        precision: SecondsFormat,
+        use_z: bool,
+    },
+    /// Number of seconds since the Unix epoch.
+    EpochSeconds,
+    /// Number of milliseconds since the Unix epoch.
+    EpochMillis,
+    /// Number of microseconds since the Unix epoch.
+    EpochMicros,
+    /// Number of nanoseconds since the Unix epoch. Written as a string after the year 2262, when it no longer fits in an `i64`.
+    EpochNanos,
+    /// `strftime` format string, e.g. `%Y-%m-%d %H:%M:%S%.3f`. Falls back to RFC3339 if invalid.
+    Custom(String),
+}
+
+impl Default for TimestampFormat {
+    /// RFC3339 with as many fractional digits as needed and a `+00:00` offset.
+    fn default() -> Self {
+        Self::Rfc3339 {
+            precision: SecondsFormat::AutoSi,
+            use_z: false,
+        }
+    }
+}
+
+impl TimestampFormat {
+    pub(crate) fn format(&self, timestamp: DateTime<Utc>) -> Value {
+        match self {
+            Self::Rfc3339 { precision, use_z } => {
+                json!(timestamp.to_rfc3339_opts(*precision, *use_z))
+            }
+            Self::EpochSeconds => json!(timestamp.timestamp()),
+            Self::EpochMillis => json!(timestamp.timestamp_millis()),
+            Self::EpochMicros => json!(timestamp.timestamp_micros()),
+            Self::EpochNanos => timestamp.timestamp_nanos_opt().map_or_else(
+                || {
+                    let nanos = i128::from(timestamp.timestamp()) * 1_000_000_000
+                        + i128::from(timestamp.timestamp_subsec_nanos());
+                    json!(nanos.to_string())
+                },
+                |nanos| json!(nanos),
+            ),
+            Self::Custom(format) => {
+                let mut formatted = String::new();
+                match write!(formatted, "{}", timestamp.format(format)) {
+                    Ok(()) => json!(formatted),
+                    Err(_) => json!(timestamp.to_rfc3339()),
+                }
+            }
+        }
+    }
+
+    /// Reads back a timestamp written with this format.
+    pub(crate) fn parse(&self, value: &Value) -> Option<DateTime<Utc>> {
+        match (self, value) {
+            (Self::EpochSeconds, Value::Number(n)) => DateTime::from_timestamp(n.as_i64()?, 0),
+            (Self::EpochMillis, Value::Number(n)) => DateTime::from_timestamp_millis(n.as_i64()?),
+            (Self::EpochMicros, Value::Number(n)) => DateTime::from_timestamp_micros(n.as_i64()?),
+            (Self::EpochNanos, Value::Number(n)) => {
+                Some(DateTime::from_timestamp_nanos(n.as_i64()?))
+            }
+            (Self::EpochNanos, Value::String(s)) => {
+                let nanos: i128 = s.parse().ok()?;
+                let seconds = i64::try_from(nanos.div_euclid(1_000_000_000)).ok()?;
+                let nanos = u32::try_from(nanos.rem_euclid(1_000_000_000)).ok()?;
+                DateTime::from_timestamp(seconds, nanos)
+            }
+            (Self::Custom(format), Value::String(s)) => DateTime::parse_from_str(s, format)
+                .map(|timestamp| timestamp.with_timezone(&Utc))
+                .or_else(|_| NaiveDateTime::parse_from_str(s, format).map(|t| t.and_utc()))
+                .or_else(|_| DateTime::parse_from_rfc3339(s).map(|t| t.with_timezone(&Utc)))
+                .ok(),
+            (_, Value::String(s)) => DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|timestamp| timestamp.with_timezone(&Utc)),
+            _ => None,
+        }
+    }
+}
+
+/// Limits applied when turning `Valuable` fields into JSON. Only used with the `valuable` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValuableLimits {
+    /// Values nested deeper than this are replaced by `"..."`.
+    pub max_depth: usize,
+    /// Only the first `max_len` items of every list, map or struct are kept.
+    pub max_len: usize,
+}
+
+impl Default for ValuableLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            max_len: 128,
+        }
+    }
+}
+
+/// Thread and task metadata added to every log. Everything is disabled by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreadFields {
+    /// Adds the name of the thread emitting the event under `thread.name`, when it has one.
+    pub name: bool,
+    /// Adds the id of the thread emitting the event under `thread.id`.
+    /// It's a process-local number assigned to each thread, which may differ from its `std::thread::ThreadId`.
+    pub id: bool,
+    /// Adds the id of the Tokio task emitting the event under `task.id`, when there's one.
+    pub task_id: bool,
+}
+
+impl ThreadFields {
+    /// Every field enabled.
+    #[must_use]
+    pub const fn all() -> Self {
+        Self {
+            name: true,
+            id: true,
+            task_id: true,
+        }
+    }
+
+    pub(crate) const fn any(self) -> bool {
+        self.name || self.id || self.task_id
+    }
+}
+
+/// Metadata of the callsite of the event added to every log. Everything is disabled by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallsiteFields {
+    /// Adds the module path of the callsite under `module_path`.
+    pub module_path: bool,
+    /// Adds the name of the callsite under `event.name`, e.g. `event src/main.rs:12`. It's never nested.
+    pub event_name: bool,
+    /// Adds a stable hash of the callsite under `fingerprint`, to group the logs of the same statement.
+    ///
+    /// Log pattern features usually hash the message template, but the message is already formatted when
+    /// it reaches the layer, so the field names are hashed instead, along with the file, line and level.
+    pub fingerprint: bool,
+}
+
+impl CallsiteFields {
+    /// Every field enabled.
+    #[must_use]
+    pub const fn all() -> Self {
+        Self {
+            module_path: true,
+            event_name: true,
+            fingerprint: true,
+        }
+    }
+}
+
+/// Options of the [`crate::LogLayer`].
+#[derive(Debug, Clone, Default)]
+pub struct LogLayerOptions {
+    pub json_parsing: JsonParsing,
+    pub large_integers: LargeIntegers,
+    pub bytes_encoding: BytesEncoding,
+    pub valuable_limits: ValuableLimits,
+    /// Expands dotted field names into nested objects, e.g. `http.method` into `{"http": {"method": ..}}`.
+    ///
+    /// A field is kept under its full dotted name when it conflicts with a previous one,
+    /// i.e. when a segment of its path already holds a value that isn't an object,
+    /// or when its last segment already holds an object.
+    /// A field without dots replaces an object created by previous dotted fields,
+    /// whose entries are then kept under their full dotted names.
+    pub expand_dotted_keys: bool,
+    pub span_format: SpanFormat,
+    /// Adds the names of the spans under `span_path`, root first, e.g. `handle_request>db_query`.
+    pub span_path: bool,
+    pub span_events: SpanEvents,
+    /// Static fields added to every log, like `env` or `region`.
+    pub context: Map<String, Value>,
+    /// Detected fields added to every log. Static ones with the same name take precedence.
+    pub context_providers: Vec<ContextProvider>,
+    pub processors: ProcessorChain,
+    pub field_filter: FieldFilter,
+    pub schema: Schema,
+    /// Format of the timestamp. When unset, the vendor crates pick the one their API prefers,
+    /// and [`TimestampFormat::default`] is used otherwise.
+    pub timestamp_format: Option<TimestampFormat>,
+    /// Key of the timestamp, `timestamp` when unset.
+    pub timestamp_key: Option<String>,
+    pub thread_fields: ThreadFields,
+    pub callsite_fields: CallsiteFields,
+}
+
+impl LogLayerOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_json_parsing(mut self, json_parsing: JsonParsing) -> Self {
+        self.json_parsing = json_parsing;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_large_integers(mut self, large_integers: LargeIntegers) -> Self {
+        self.large_integers = large_integers;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_bytes_encoding(mut self, bytes_encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = bytes_encoding;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_valuable_limits(mut self, valuable_limits: ValuableLimits) -> Self {
+        self.valuable_limits = valuable_limits;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_expand_dotted_keys(mut self, expand_dotted_keys: bool) -> Self {
+        self.expand_dotted_keys = expand_dotted_keys;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_span_format(mut self, span_format: SpanFormat) -> Self {
+        self.span_format = span_format;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_span_path(mut self, span_path: bool) -> Self {
+        self.span_path = span_path;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_span_events(mut self, span_events: SpanEvents) -> Self {
+        self.span_events = span_events;
+        self
+    }
+
+    /// Adds a static field to every log. Fields of the events and spans take precedence over it.
+    #[must_use]
+    pub fn with_context(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.context.insert(key.into(), value.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_context_provider(mut self, provider: ContextProvider) -> Self {
+        self.context_providers.push(provider);
+        self
+    }
+
+    #[must_use]
+    pub fn with_field_filter(mut self, field_filter: FieldFilter) -> Self {
+        self.field_filter = field_filter;
+        self
+    }
+
+    #[must_use]
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    #[must_use]
+    pub fn with_timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = Some(timestamp_format);
+        self
+    }
+
+    #[must_use]
+    pub fn with_timestamp_key(mut self, timestamp_key: impl Into<String>) -> Self {
+        self.timestamp_key = Some(timestamp_key.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn with_thread_fields(mut self, thread_fields: ThreadFields) -> Self {
+        self.thread_fields = thread_fields;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_callsite_fields(mut self, callsite_fields: CallsiteFields) -> Self {
+        self.callsite_fields = callsite_fields;
+        self
+    }
+
+    /// Appends a processor to the chain run on every log.
+    #[must_use]
+    pub fn with_processor(mut self, processor: impl LogProcessor + 'static) -> Self {
+        self.processors.push(processor);
+        self
+    }
+
+    pub(crate) fn timestamp_key(&self) -> &str {
+        self.timestamp_key.as_deref().unwrap_or("timestamp")
+    }
+
+    /// Runs the context providers and merges their values with the static context.
+    pub(crate) fn resolve_context(&self) -> Map<String, Value> {
+        let mut context: Map<String, Value> = self
+            .context_providers
+            .iter()
+            .filter_map(ContextProvider::provide)
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        context.extend(self.context.clone());
+        context
+    }
+}
@@ -0,0 +1,64 @@
+use crate::log_ingestor::Log;
+use std::{fmt, sync::Arc};
+
+/// What to do with a log after processing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Keep,
+    /// The log is discarded and the next processors don't see it.
+    Drop,
+}
+
+/// Modifies or filters logs before they reach the ingestor.
+///
+/// Processors run in the ingestion thread, in the order they were added to the [`crate::LogLayerOptions`].
+/// Closures taking a `&mut Log` and returning a [`Decision`] are processors too.
+pub trait LogProcessor: Send + Sync {
+    fn process(&self, log: &mut Log) -> Decision;
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F> LogProcessor for F
+where
+    F: Fn(&mut Log) -> Decision + Send + Sync,
+{
+    fn process(&self, log: &mut Log) -> Decision {
+        self(log)
+    }
+}
+
+/// Ordered list of [`LogProcessor`]s.
+#[derive(Clone, Default)]
+pub struct ProcessorChain(Vec<Arc<dyn LogProcessor>>);
+
+impl ProcessorChain {
+    pub fn push(&mut self, processor: impl LogProcessor + 'static) {
+        self.0.push(Arc::new(processor));
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Runs the processors until one of them drops the log.
+    pub fn process(&self, log: &mut Log) -> Decision {
+        for processor in &self.0 {
+            if processor.process(log) == Decision::Drop {
+                return Decision::Drop;
+            }
+        }
+        Decision::Keep
+    }
+}
+
+impl fmt::Debug for ProcessorChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|processor| processor.name()))
+            .finish()
+    }
+}
@@ -0,0 +1,328 @@
+use crate::layer::METADATA_KEYS;
+use crate::log_ingestor::Log;
+use crate::processor::{Decision, LogProcessor};
+use hmac::{Hmac, Mac};
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use std::fmt::Write;
+
+/// Timings of the span `close` events.
+const SPAN_TIMINGS: &[&str] = &["duration_ns", "busy_ns", "idle_ns"];
+
+/// How a sensitive value is replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactAction {
+    /// The value is replaced by the mask, `[REDACTED]` by default.
+    Mask,
+    /// The value is replaced by its salted HMAC-SHA256, so that it can still be correlated across logs.
+    /// Falls back to [`RedactAction::Mask`] if no secret was provided.
+    Hash,
+}
+
+/// Well-known sensitive values.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Email,
+    /// 13 to 19 digits, optionally separated by spaces or dashes, passing the Luhn check.
+    CreditCard,
+    Jwt,
+    /// AWS access key ids, e.g. `AKIA...`.
+    AwsAccessKey,
+    /// `Bearer` tokens, e.g. in `Authorization` headers.
+    BearerToken,
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn into_regex(self) -> Regex {
+        let pattern = match self {
+            Self::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            Self::CreditCard => r"\b\d(?:[ -]?\d){12,18}\b",
+            Self::Jwt => r"\beyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*",
+            Self::AwsAccessKey => r"\b(?:AKIA|ASIA|ABIA|ACCA)[A-Z0-9]{16}\b",
+            Self::BearerToken => r"(?i)\bbearer\s+[A-Za-z0-9._~+/-]+=*",
+            Self::Regex(regex) => return regex,
+        };
+        Regex::new(pattern).expect("built-in patterns are valid")
+    }
+}
+
+/// Returns `true` if the digits of the value pass the Luhn check.
+fn luhn(value: &str) -> bool {
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Case-insensitive glob supporting `*` and `?`.
+fn glob_matches(glob: &[char], name: &[char]) -> bool {
+    match (glob.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some(('*', rest)), _) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(glob, &name[1..]))
+        }
+        (Some(('?', rest)), Some((_, name))) => glob_matches(rest, name),
+        (Some((g, rest)), Some((n, name))) if g == n => glob_matches(rest, name),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct KeyRule {
+    glob: Vec<char>,
+    action: RedactAction,
+}
+
+#[derive(Debug, Clone)]
+struct ValueRule {
+    regex: Regex,
+    luhn: bool,
+    action: RedactAction,
+}
+
+/// [`LogProcessor`] redacting sensitive data from the logs, including nested objects and span fields.
+///
+/// Values are redacted entirely when their key matches one of the key globs,
+/// and partially when they contain one of the patterns.
+///
+/// The ids, timestamps and timings added by the layer are left untouched, as they may look like card numbers.
+/// Use [`Redactor::with_ignored_key`] for a custom timestamp key, see [`crate::LogLayerOptions::with_timestamp_key`].
+///
+/// ```rust
+/// use log_tracing_layer::{LogLayerOptions, Pattern, Redactor};
+///
+/// let redactor = Redactor::pii()
+///     .with_key("*session*")
+///     .with_hmac_secret("my-secret")
+///     .with_pattern_hashed(Pattern::Email);
+/// let options = LogLayerOptions::new().with_processor(redactor);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    keys: Vec<KeyRule>,
+    values: Vec<ValueRule>,
+    mask: String,
+    hmac_secret: Option<Vec<u8>>,
+    ignored_keys: Vec<String>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self {
+            keys: vec![],
+            values: vec![],
+            mask: "[REDACTED]".to_string(),
+            hmac_secret: None,
+            ignored_keys: vec!["timestamp".to_string()],
+        }
+    }
+}
+
+impl Redactor {
+    /// Creates a redactor without any rule.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a redactor masking passwords, secrets, tokens, authorization headers and api keys,
+    /// along with every built-in pattern.
+    #[must_use]
+    pub fn pii() -> Self {
+        Self::new()
+            .with_key("*password*")
+            .with_key("*passwd*")
+            .with_key("*secret*")
+            .with_key("*token*")
+            .with_key("*authorization*")
+            .with_key("*api_key*")
+            .with_key("*apikey*")
+            .with_pattern(Pattern::Email)
+            .with_pattern(Pattern::CreditCard)
+            .with_pattern(Pattern::Jwt)
+            .with_pattern(Pattern::AwsAccessKey)
+            .with_pattern(Pattern::BearerToken)
+    }
+
+    #[must_use]
+    pub fn with_mask(mut self, mask: impl Into<String>) -> Self {
+        self.mask = mask.into();
+        self
+    }
+
+    /// Secret used as the key of the HMAC of the hashed values.
+    #[must_use]
+    pub fn with_hmac_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.hmac_secret = Some(secret.as_ref().to_vec());
+        self
+    }
+
+    /// Leaves the value of a top-level key untouched, like the keys added by the layer.
+    #[must_use]
+    pub fn with_ignored_key(mut self, key: impl Into<String>) -> Self {
+        self.ignored_keys.push(key.into());
+        self
+    }
+
+    /// Masks the values of the keys matching the glob.
+    #[must_use]
+    pub fn with_key(self, glob: &str) -> Self {
+        self.with_key_action(glob, RedactAction::Mask)
+    }
+
+    /// Hashes the values of the keys matching the glob.
+    #[must_use]
+    pub fn with_key_hashed(self, glob: &str) -> Self {
+        self.with_key_action(glob, RedactAction::Hash)
+    }
+
+    #[must_use]
+    pub fn with_key_action(mut self, glob: &str, action: RedactAction) -> Self {
+        self.keys.push(KeyRule {
+            glob: glob.to_lowercase().chars().collect(),
+            action,
+        });
+        self
+    }
+
+    /// Masks the parts of the values matching the pattern.
+    #[must_use]
+    pub fn with_pattern(self, pattern: Pattern) -> Self {
+        self.with_pattern_action(pattern, RedactAction::Mask)
+    }
+
+    /// Hashes the parts of the values matching the pattern.
+    #[must_use]
+    pub fn with_pattern_hashed(self, pattern: Pattern) -> Self {
+        self.with_pattern_action(pattern, RedactAction::Hash)
+    }
+
+    #[must_use]
+    pub fn with_pattern_action(mut self, pattern: Pattern, action: RedactAction) -> Self {
+        let luhn = matches!(pattern, Pattern::CreditCard);
+        self.values.push(ValueRule {
+            regex: pattern.into_regex(),
+            luhn,
+            action,
+        });
+        self
+    }
+
+    fn replacement(&self, value: &str, action: RedactAction) -> String {
+        let Some(secret) = self
+            .hmac_secret
+            .as_ref()
+            .filter(|_| action == RedactAction::Hash)
+        else {
+            return self.mask.clone();
+        };
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(value.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .fold("hmac:".to_string(), |mut hash, byte| {
+                // writing to a `String` never fails
+                let _ = write!(hash, "{byte:02x}");
+                hash
+            })
+    }
+
+    fn key_action(&self, key: &str) -> Option<RedactAction> {
+        let key: Vec<char> = key.to_lowercase().chars().collect();
+        self.keys
+            .iter()
+            .find(|rule| glob_matches(&rule.glob, &key))
+            .map(|rule| rule.action)
+    }
+
+    /// Returns the redacted text, if any pattern matched.
+    fn redact_text(&self, text: &str) -> Option<String> {
+        let mut redacted: Option<String> = None;
+        for rule in &self.values {
+            let current = redacted.as_deref().unwrap_or(text);
+            let mut matched = false;
+            let replaced = rule.regex.replace_all(current, |caps: &Captures| {
+                let found = &caps[0];
+                if rule.luhn && !luhn(found) {
+                    return found.to_string();
+                }
+                matched = true;
+                self.replacement(found, rule.action)
+            });
+            if matched {
+                redacted = Some(replaced.into_owned());
+            }
+        }
+        redacted
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match &mut *value {
+            Value::Object(map) => self.redact_map(map),
+            Value::Array(values) => values.iter_mut().for_each(|v| self.redact_value(v)),
+            Value::String(text) => {
+                if let Some(redacted) = self.redact_text(text) {
+                    *text = redacted;
+                }
+            }
+            // e.g. card numbers logged as integers
+            Value::Number(number) => {
+                let number = number.to_string();
+                if let Some(redacted) = self.redact_text(&number) {
+                    *value = Value::String(redacted);
+                }
+            }
+            Value::Bool(_) | Value::Null => {}
+        }
+    }
+
+    fn redact_entry(&self, key: &str, value: &mut Value) {
+        match self.key_action(key) {
+            Some(action) => {
+                let plain = match &*value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                *value = Value::String(self.replacement(&plain, action));
+            }
+            None => self.redact_value(value),
+        }
+    }
+
+    fn redact_map(&self, map: &mut Map<String, Value>) {
+        for (key, value) in map.iter_mut() {
+            self.redact_entry(key, value);
+        }
+    }
+
+    /// Returns `true` for the top-level keys which never hold user data.
+    /// The span fields are still redacted.
+    fn ignores(&self, key: &str) -> bool {
+        (METADATA_KEYS.contains(&key) && !matches!(key, "span" | "spans"))
+            || SPAN_TIMINGS.contains(&key)
+            || self.ignored_keys.iter().any(|ignored| ignored == key)
+    }
+}
+
+impl LogProcessor for Redactor {
+    fn process(&self, log: &mut Log) -> Decision {
+        for (key, value) in log.iter_mut() {
+            if !self.ignores(key) {
+                self.redact_entry(key, value);
+            }
+        }
+        Decision::Keep
+    }
+}
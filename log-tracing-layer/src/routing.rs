@@ -0,0 +1,412 @@
+use crate::batch_ingestor::{BatchLogIngestor, FlushReport, IngestError, IngestorHandle};
+use crate::log_ingestor::Log;
+use crate::schema::Schema;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{fmt, str::FromStr, time::Duration};
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+/// Error returned when a [`RouteFilter`] can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteFilterError {
+    directive: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for RouteFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid directive `{}`: {}", self.directive, self.reason)
+    }
+}
+
+impl std::error::Error for RouteFilterError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FieldMatch {
+    name: String,
+    value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directive {
+    target: Option<String>,
+    span: Option<String>,
+    fields: Vec<FieldMatch>,
+    level: LevelFilter,
+}
+
+impl Directive {
+    /// More specific directives win, just like in `EnvFilter`.
+    fn specificity(&self) -> (bool, usize) {
+        let has_span = self.span.is_some() || !self.fields.is_empty();
+        let target_len = self.target.as_ref().map_or(0, String::len);
+        (has_span, target_len)
+    }
+
+    fn applies_to(&self, log: &Log) -> bool {
+        if let Some(target) = &self.target {
+            match log.get("target").and_then(Value::as_str) {
+                Some(t) if t.starts_with(target.as_str()) => {}
+                _ => return false,
+            }
+        }
+
+        if self.span.is_none() {
+            // fields can be found either in the event or in any of its spans
+            return self.fields.iter().all(|f| {
+                f.matches(log) || spans(log).any(|s| s.as_object().is_some_and(|s| f.matches(s)))
+            });
+        }
+
+        spans(log).filter_map(Value::as_object).any(|span| {
+            let name = span.get("name").and_then(Value::as_str);
+            name == self.span.as_deref() && self.fields.iter().all(|f| f.matches(span))
+        })
+    }
+}
+
+impl FieldMatch {
+    fn matches(&self, fields: &Log) -> bool {
+        // the field may have been expanded into nested objects
+        let value = fields.get(&self.name).or_else(|| {
+            let (first, rest) = self.name.split_once('.')?;
+            rest.split('.')
+                .try_fold(fields.get(first)?, |value, segment| value.get(segment))
+        });
+        match (value, &self.value) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(Value::String(s)), Some(expected)) => s == expected,
+            (Some(v), Some(expected)) => &v.to_string() == expected,
+        }
+    }
+}
+
+/// Spans of the log, falling back to the leaf span when the list is not kept.
+fn spans(log: &Log) -> impl Iterator<Item = &Value> {
+    let list = log.get("spans").and_then(Value::as_array);
+    let leaf = list.is_none().then(|| log.get("span")).flatten();
+    list.into_iter().flatten().chain(leaf)
+}
+
+fn parse_level(s: &str) -> Option<LevelFilter> {
+    LevelFilter::from_str(s).ok()
+}
+
+/// Splits on commas that are not enclosed in `[...]` or `{...}`.
+fn split_directives(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+impl FromStr for Directive {
+    type Err = RouteFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason| RouteFilterError {
+            directive: s.to_string(),
+            reason,
+        };
+
+        // a lonely level applies to every log
+        if let Some(level) = parse_level(s) {
+            return Ok(Self {
+                target: None,
+                span: None,
+                fields: vec![],
+                level,
+            });
+        }
+
+        let (selector, level) = match s.rsplit_once('=') {
+            // `=` inside braces belongs to a field, not to the level
+            Some((selector, level)) if !level.contains(['}', ']']) => (
+                selector,
+                parse_level(level.trim()).ok_or_else(|| err("unknown level"))?,
+            ),
+            _ => (s, LevelFilter::TRACE),
+        };
+
+        let (target, span_part) = match selector.find('[') {
+            Some(start) => {
+                let end = selector
+                    .rfind(']')
+                    .filter(|end| *end == selector.len() - 1)
+                    .ok_or_else(|| err("missing closing `]`"))?;
+                (&selector[..start], Some(&selector[start + 1..end]))
+            }
+            None => (selector, None),
+        };
+
+        let mut span = None;
+        let mut fields = vec![];
+        if let Some(span_part) = span_part {
+            let (name, field_part) = match span_part.find('{') {
+                Some(start) => {
+                    if !span_part.ends_with('}') {
+                        return Err(err("missing closing `}`"));
+                    }
+                    (
+                        &span_part[..start],
+                        Some(&span_part[start + 1..span_part.len() - 1]),
+                    )
+                }
+                None => (span_part, None),
+            };
+            if !name.trim().is_empty() {
+                span = Some(name.trim().to_string());
+            }
+            for field in field_part.into_iter().flat_map(|f| f.split(',')) {
+                let field = field.trim();
+                if field.is_empty() {
+                    continue;
+                }
+                let (name, value) = match field.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim().to_string())),
+                    None => (field, None),
+                };
+                fields.push(FieldMatch {
+                    name: name.to_string(),
+                    value,
+                });
+            }
+        }
+
+        let target = target.trim();
+        if target.contains(|c: char| c.is_whitespace() || c == '{' || c == '}') {
+            return Err(err("invalid target"));
+        }
+
+        Ok(Self {
+            target: (!target.is_empty()).then(|| target.to_string()),
+            span,
+            fields,
+            level,
+        })
+    }
+}
+
+/// A set of `EnvFilter`-like directives deciding which logs a route receives.
+///
+/// Directives are separated by commas and follow the `target[span{field=value}]=level` syntax,
+/// where every part is optional:
+///
+/// - `warn`: every log with level `WARN` or above.
+/// - `audit`: every log whose target starts with `audit`.
+/// - `my_crate::db=debug`: logs from `my_crate::db` with level `DEBUG` or above.
+/// - `[handle_request]=info`: logs inside a span named `handle_request`.
+/// - `[{user_id}]`: logs with a `user_id` field, either in the event or in any span.
+/// - `[{status=500}]=error`: logs with a `status` field whose value is `500`.
+///
+/// As with `EnvFilter`, when several directives match a log, the most specific one decides
+/// the level, so `warn,my_crate::db=debug` routes `DEBUG` logs from `my_crate::db` only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteFilter {
+    directives: Vec<Directive>,
+}
+
+impl RouteFilter {
+    /// Returns `true` if the log must be sent to the route.
+    #[must_use]
+    pub fn matches(&self, log: &Log) -> bool {
+        let Some(level) = log
+            .get("level")
+            .and_then(Value::as_str)
+            .and_then(|l| Level::from_str(l).ok())
+        else {
+            return false;
+        };
+
+        self.directives
+            .iter()
+            .filter(|d| d.applies_to(log))
+            .max_by_key(|d| d.specificity())
+            .is_some_and(|d| level <= d.level)
+    }
+}
+
+impl FromStr for RouteFilter {
+    type Err = RouteFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let directives = split_directives(s)
+            .into_iter()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(Directive::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { directives })
+    }
+}
+
+struct Route {
+    name: String,
+    filter: RouteFilter,
+    ingestor: Box<dyn BatchLogIngestor>,
+    /// Whether the ingestor applies the schema itself, e.g. a nested router.
+    applies_schema: bool,
+}
+
+/// A [`BatchLogIngestor`] dispatching every log to the named ingestors whose [`RouteFilter`] matches it.
+///
+/// A log matching several routes is sent to all of them.
+/// Every route is flushed at the shortest of their flush intervals and on shutdown.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let router = Router::new()
+///     .route("datadog", "warn".parse()?, datadog)
+///     .route("audit", "audit".parse()?, audit_file)
+///     .route("new-relic", "my_crate::db=debug".parse()?, new_relic);
+/// let layer = LogLayer::from_batch_ingestor(router);
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    schema: Schema,
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field(
+                "routes",
+                &self.routes.iter().map(|r| &r.name).collect::<Vec<_>>(),
+            )
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+impl Router {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a named route. Routes are evaluated in the order they were added.
+    #[must_use]
+    pub fn route<I>(mut self, name: impl Into<String>, filter: RouteFilter, ingestor: I) -> Self
+    where
+        I: BatchLogIngestor + 'static,
+    {
+        self.routes.push(Route {
+            name: name.into(),
+            filter,
+            ingestor: Box::new(ingestor),
+            applies_schema: false,
+        });
+        self
+    }
+}
+
+#[async_trait]
+impl BatchLogIngestor for Router {
+    fn name(&self) -> &'static str {
+        "router"
+    }
+
+    fn accept_schema(&mut self, schema: &Schema) -> bool {
+        for route in &mut self.routes {
+            route.applies_schema = route.ingestor.accept_schema(schema);
+        }
+        self.schema = schema.clone();
+        true
+    }
+
+    fn start(&mut self) -> IngestorHandle {
+        let mut handle = IngestorHandle::new();
+        for route in &mut self.routes {
+            handle.extend(route.ingestor.start());
+        }
+        handle
+    }
+
+    fn flush_interval(&self) -> Option<Duration> {
+        self.routes
+            .iter()
+            .filter_map(|r| r.ingestor.flush_interval())
+            .min()
+    }
+
+    async fn ingest_batch(&mut self, logs: Vec<Log>) {
+        let mut batches = vec![vec![]; self.routes.len()];
+        for log in logs {
+            let mut matching = self
+                .routes
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.filter.matches(&log))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            // the last route can take ownership of the log
+            if let Some(last) = matching.pop() {
+                for i in matching {
+                    batches[i].push(log.clone());
+                }
+                batches[last].push(log);
+            }
+        }
+
+        for (route, mut batch) in self.routes.iter_mut().zip(batches) {
+            if batch.is_empty() {
+                continue;
+            }
+            if !route.applies_schema {
+                for log in &mut batch {
+                    self.schema.apply(log);
+                }
+            }
+            route.ingestor.ingest_batch(batch).await;
+        }
+    }
+
+    async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+        // every route is flushed even if some of them fail
+        let mut report = FlushReport::default();
+        let mut error = None;
+        for route in &mut self.routes {
+            match route.ingestor.flush().await {
+                Ok(r) => report = report.merge(r),
+                Err(e) => {
+                    eprintln!("Failed to flush route {}: {e}", route.name);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        error.map_or(Ok(report), Err)
+    }
+
+    async fn shutdown(&mut self) -> Result<FlushReport, IngestError> {
+        // every route is shut down even if some of them fail
+        let mut report = FlushReport::default();
+        let mut error = None;
+        for route in &mut self.routes {
+            match route.ingestor.shutdown().await {
+                Ok(r) => report = report.merge(r),
+                Err(e) => {
+                    eprintln!("Failed to shut down route {}: {e}", route.name);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        error.map_or(Ok(report), Err)
+    }
+}
@@ -0,0 +1,100 @@
+use crate::ecs::MAPPINGS;
+use crate::layer::insert_dotted;
+use crate::log_ingestor::Log;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// Top-level key, even if it contains dots.
+    Key(String),
+    /// Dotted path into nested objects.
+    Path(String),
+}
+
+/// Renames or moves top-level keys of the logs, once they've gone through the processors.
+///
+/// Mappings are applied in order and overwrite any existing value.
+/// A [`crate::Router`] applies it to the logs of each route once they've been routed,
+/// so that its directives still read the `level`, `target` and `spans` keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    mappings: Vec<(String, Target)>,
+}
+
+impl Schema {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renames a top-level key: `rename("target", "code.namespace")` produces `"code.namespace": ..`.
+    #[must_use]
+    pub fn rename(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.mappings.push((from.into(), Target::Key(to.into())));
+        self
+    }
+
+    /// Moves a top-level key into nested objects: `nest("target", "logger.name")` produces `"logger": { "name": .. }`.
+    ///
+    /// If the path conflicts with an existing value, the dotted path is used as a top-level key.
+    #[must_use]
+    pub fn nest(mut self, from: impl Into<String>, path: impl Into<String>) -> Self {
+        self.mappings.push((from.into(), Target::Path(path.into())));
+        self
+    }
+
+    /// Datadog [standard attributes](https://docs.datadoghq.com/logs/log_configuration/attributes_naming_convention/).
+    #[must_use]
+    pub fn datadog() -> Self {
+        Self::new()
+            .rename("level", "status")
+            .nest("target", "logger.name")
+            .rename("timestamp", "date")
+    }
+
+    /// [Elastic Common Schema](https://www.elastic.co/guide/en/ecs/current/ecs-field-reference.html) fields,
+    /// with the same mappings as the [`crate::EcsFormatter`].
+    ///
+    /// It only moves keys: the formatter also lowercases the level, maps errors and trace ids,
+    /// and adds `ecs.version`.
+    #[must_use]
+    pub fn ecs() -> Self {
+        let schema = Self::new()
+            .rename("timestamp", "@timestamp")
+            .nest("level", "log.level");
+        MAPPINGS
+            .iter()
+            .filter(|(key, field)| key != field)
+            .fold(schema, |schema, (key, field)| schema.nest(*key, *field))
+    }
+
+    /// OpenTelemetry [log data model](https://opentelemetry.io/docs/specs/otel/logs/data-model/)
+    /// and [code attributes](https://opentelemetry.io/docs/specs/semconv/attributes-registry/code/).
+    #[must_use]
+    pub fn opentelemetry() -> Self {
+        Self::new()
+            .rename("level", "severity_text")
+            .rename("message", "body")
+            .rename("target", "code.namespace")
+            .rename("file", "code.filepath")
+            .rename("line", "code.lineno")
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    pub fn apply(&self, log: &mut Log) {
+        for (from, target) in &self.mappings {
+            let Some(value) = log.remove(from) else {
+                continue;
+            };
+            match target {
+                Target::Key(key) => {
+                    log.insert(key.clone(), value);
+                }
+                Target::Path(path) => insert_dotted(log, path, value),
+            }
+        }
+    }
+}
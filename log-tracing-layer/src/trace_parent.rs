@@ -0,0 +1,117 @@
+use std::{fmt, str::FromStr};
+
+/// Name of the span field holding a W3C `traceparent`, recognized by the [`crate::LogLayer`].
+pub const TRACEPARENT_FIELD: &str = "traceparent";
+/// Name of the span field holding a W3C `tracestate`, recognized by the [`crate::LogLayer`].
+pub const TRACESTATE_FIELD: &str = "tracestate";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParentError {
+    value: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for TraceParentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid traceparent `{}`: {}", self.value, self.reason)
+    }
+}
+
+impl std::error::Error for TraceParentError {}
+
+/// Remote trace context received in a W3C `traceparent` header.
+///
+/// Record it in a span under the [`TRACEPARENT_FIELD`] field and every log inside the span
+/// will carry its `trace_id` and `parent_span_id`:
+///
+/// ```rust
+/// use log_tracing_layer::TraceParent;
+///
+/// let parent = TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+/// let span = tracing::info_span!("request", traceparent = %parent);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    pub trace_id: u128,
+    pub parent_id: u64,
+    pub flags: u8,
+    /// Vendor specific data received in the `tracestate` header.
+    pub tracestate: Option<String>,
+}
+
+impl TraceParent {
+    /// Parses a `traceparent` header.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value doesn't follow the W3C Trace Context format.
+    pub fn parse(traceparent: &str) -> Result<Self, TraceParentError> {
+        traceparent.parse()
+    }
+
+    #[must_use]
+    pub fn with_tracestate(mut self, tracestate: impl Into<String>) -> Self {
+        let tracestate = tracestate.into();
+        self.tracestate = (!tracestate.trim().is_empty()).then_some(tracestate);
+        self
+    }
+
+    #[must_use]
+    pub const fn is_sampled(&self) -> bool {
+        self.flags & 1 == 1
+    }
+}
+
+/// Parses a fixed length lowercase hexadecimal part.
+fn parse_hex(part: &str, len: usize) -> Option<u128> {
+    let valid = part.len() == len && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    valid.then(|| u128::from_str_radix(part, 16).ok()).flatten()
+}
+
+impl FromStr for TraceParent {
+    type Err = TraceParentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason| TraceParentError {
+            value: s.to_string(),
+            reason,
+        };
+
+        let mut parts = s.trim().split('-');
+        let mut next = || parts.next().unwrap_or_default();
+        let version = parse_hex(next(), 2).ok_or_else(|| err("invalid version"))?;
+        let trace_id = parse_hex(next(), 32).ok_or_else(|| err("invalid trace id"))?;
+        let parent_id = parse_hex(next(), 16).ok_or_else(|| err("invalid parent id"))?;
+        let flags = parse_hex(next(), 2).ok_or_else(|| err("invalid flags"))?;
+
+        if version == 0xff {
+            return Err(err("forbidden version"));
+        }
+        // future versions may add parts, but version 00 can't
+        if version == 0 && parts.next().is_some() {
+            return Err(err("unexpected data after the flags"));
+        }
+        if trace_id == 0 || parent_id == 0 {
+            return Err(err("all zeroes ids are invalid"));
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(Self {
+            trace_id,
+            parent_id: parent_id as u64,
+            flags: flags as u8,
+            tracestate: None,
+        })
+    }
+}
+
+/// Formats the `traceparent`, with the version 00.
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
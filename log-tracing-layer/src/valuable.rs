@@ -0,0 +1,159 @@
+use crate::options::{LargeIntegers, ValuableLimits};
+use serde_json::{json, Map, Value};
+use valuable::{Fields, NamedValues, Slice, Visit};
+
+/// Placeholder for the values nested deeper than [`ValuableLimits::max_depth`].
+const TRUNCATED: &str = "...";
+
+/// Turns a [`valuable::Value`] into a nested JSON value.
+///
+/// Structs and maps become objects, lists and tuples become arrays and enums follow the
+/// externally tagged representation of serde: `"Variant"` or `{ "Variant": .. }`.
+pub fn to_json(
+    value: valuable::Value<'_>,
+    limits: ValuableLimits,
+    large_integers: LargeIntegers,
+) -> Value {
+    Converter {
+        limits,
+        large_integers,
+    }
+    .convert(value, 0)
+}
+
+#[derive(Clone, Copy)]
+struct Converter {
+    limits: ValuableLimits,
+    large_integers: LargeIntegers,
+}
+
+impl Converter {
+    fn convert(self, value: valuable::Value<'_>, depth: usize) -> Value {
+        use valuable::Value as V;
+
+        match value {
+            V::Bool(v) => json!(v),
+            V::Char(v) => json!(v),
+            V::F32(v) => json!(v),
+            V::F64(v) => json!(v),
+            V::I8(v) => json!(v),
+            V::I16(v) => json!(v),
+            V::I32(v) => json!(v),
+            V::I64(v) => json!(v),
+            V::Isize(v) => json!(v),
+            V::U8(v) => json!(v),
+            V::U16(v) => json!(v),
+            V::U32(v) => json!(v),
+            V::U64(v) => json!(v),
+            V::Usize(v) => json!(v),
+            V::I128(v) => self.large_integers.format(v),
+            V::U128(v) => self.large_integers.format(v),
+            V::String(v) => json!(v),
+            V::Path(v) => json!(v.display().to_string()),
+            V::Error(v) => json!(v.to_string()),
+            V::Unit => Value::Null,
+            _ if depth >= self.limits.max_depth => json!(TRUNCATED),
+            V::Listable(v) => self.collect(depth, |c| v.visit(c)).unwrap_or(json!([])),
+            V::Mappable(v) => self.collect(depth, |c| v.visit(c)).unwrap_or(json!({})),
+            V::Structable(v) => {
+                let empty = match v.definition().fields() {
+                    Fields::Named(_) => json!({}),
+                    Fields::Unnamed(_) => json!([]),
+                };
+                self.collect(depth, |c| v.visit(c)).unwrap_or(empty)
+            }
+            V::Tuplable(v) => self.collect(depth, |c| v.visit(c)).unwrap_or(json!([])),
+            V::Enumerable(v) => {
+                let variant = v.variant().name().to_string();
+                match self.collect(depth, |c| v.visit(c)) {
+                    Some(fields) => json!({ variant: fields }),
+                    None => json!(variant),
+                }
+            }
+            // `valuable::Value` is non exhaustive
+            other => json!(format!("{other:?}")),
+        }
+    }
+
+    /// Returns `None` when the value has no children, so that the caller picks the empty value.
+    fn collect(self, depth: usize, visit: impl FnOnce(&mut Collector)) -> Option<Value> {
+        let mut collector = Collector {
+            converter: self,
+            depth: depth + 1,
+            output: None,
+        };
+        visit(&mut collector);
+        collector.output.map(|output| match output {
+            Output::Object(object) => Value::Object(object),
+            Output::Array(array) => Value::Array(array),
+        })
+    }
+}
+
+enum Output {
+    Object(Map<String, Value>),
+    Array(Vec<Value>),
+}
+
+/// Gathers the children of a value, up to [`ValuableLimits::max_len`] of them.
+struct Collector {
+    converter: Converter,
+    depth: usize,
+    output: Option<Output>,
+}
+
+impl Collector {
+    fn push(&mut self, value: valuable::Value<'_>) {
+        let max_len = self.converter.limits.max_len;
+        let output = self.output.get_or_insert_with(|| Output::Array(vec![]));
+        if let Output::Array(array) = output {
+            if array.len() < max_len {
+                array.push(self.converter.convert(value, self.depth));
+            }
+        }
+    }
+
+    fn insert(&mut self, key: String, value: valuable::Value<'_>) {
+        let max_len = self.converter.limits.max_len;
+        let output = self
+            .output
+            .get_or_insert_with(|| Output::Object(Map::new()));
+        if let Output::Object(object) = output {
+            if object.len() < max_len {
+                object.insert(key, self.converter.convert(value, self.depth));
+            }
+        }
+    }
+}
+
+impl Visit for Collector {
+    fn visit_value(&mut self, value: valuable::Value<'_>) {
+        self.push(value);
+    }
+
+    fn visit_named_fields(&mut self, named_values: &NamedValues<'_>) {
+        for (field, value) in named_values {
+            self.insert(field.name().to_string(), *value);
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[valuable::Value<'_>]) {
+        for value in values {
+            self.push(*value);
+        }
+    }
+
+    fn visit_primitive_slice(&mut self, slice: Slice<'_>) {
+        for value in slice {
+            self.push(value);
+        }
+    }
+
+    fn visit_entry(&mut self, key: valuable::Value<'_>, value: valuable::Value<'_>) {
+        let key = match key {
+            valuable::Value::String(key) => key.to_string(),
+            key => self.converter.convert(key, self.depth).to_string(),
+        };
+        self.insert(key, value);
+    }
+}
@@ -1,45 +1,171 @@
 use std::str::FromStr;
 
+use crate::options::LogLayerOptions;
 use serde_json::json;
 use tracing::field::Visit;
 
+/// A field value captured on the thread that emitted the event.
+///
+/// Borrowed values (`Debug` and `Error`) are formatted right away,
+/// but the conversion into JSON happens in the ingestion thread.
+#[derive(Debug, Clone)]
+pub enum FieldValue {
+    Str(String),
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    I128(i128),
+    U128(u128),
+    Bytes(Vec<u8>),
+    Error {
+        message: String,
+        kind: Option<String>,
+        chain: Vec<String>,
+    },
+    Debug(String),
+    /// Value already converted into JSON, e.g. a `Valuable` one.
+    #[cfg_attr(not(all(tracing_unstable, feature = "valuable")), allow(dead_code))]
+    Json(serde_json::Value),
+}
+
+impl FieldValue {
+    pub fn to_json(&self, parse_json: bool, options: &LogLayerOptions) -> serde_json::Value {
+        match self {
+            // try to parse the string in case it's already a json value
+            Self::Str(value) if parse_json => {
+                serde_json::Value::from_str(value).unwrap_or_else(|_| json!(value))
+            }
+            Self::Str(value) | Self::Debug(value) => json!(value),
+            Self::Bool(value) => json!(value),
+            Self::U64(value) => json!(value),
+            Self::I64(value) => json!(value),
+            Self::F64(value) => json!(value),
+            Self::I128(value) => options.large_integers.format(*value),
+            Self::U128(value) => options.large_integers.format(*value),
+            Self::Bytes(value) => options.bytes_encoding.encode(value),
+            Self::Error {
+                message,
+                kind,
+                chain,
+            } => {
+                let mut error = json!({ "message": message, "chain": chain });
+                if let Some(kind) = kind {
+                    error["kind"] = json!(kind);
+                }
+                error
+            }
+            Self::Json(value) => value.clone(),
+        }
+    }
+}
+
+/// Best effort to get the type name of an error, as `dyn Error` doesn't expose it.
+///
+/// Well-known errors are downcasted. Otherwise, the name is taken from the `Debug` output
+/// when it looks like a derived one, e.g. `MyError { .. }` or `MyError(..)`.
+fn error_kind(error: &(dyn std::error::Error + 'static)) -> Option<String> {
+    macro_rules! downcast {
+        ($($ty:ty),*) => {
+            $(
+                if error.is::<$ty>() {
+                    return Some(stringify!($ty).to_string());
+                }
+            )*
+        };
+    }
+    downcast!(
+        std::io::Error,
+        std::fmt::Error,
+        std::num::ParseIntError,
+        std::num::ParseFloatError,
+        std::str::Utf8Error,
+        std::string::FromUtf8Error,
+        serde_json::Error
+    );
+
+    let debug = format!("{error:?}");
+    let name_len = debug
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+        .unwrap_or(debug.len());
+    let (name, rest) = debug.split_at(name_len);
+    let looks_like_a_type = name.starts_with(|c: char| c.is_ascii_uppercase())
+        && (rest.is_empty() || rest.starts_with(" {") || rest.starts_with('('));
+    looks_like_a_type.then(|| name.to_string())
+}
+
+pub type Fields = Vec<(&'static str, FieldValue)>;
+
 #[allow(clippy::module_name_repetitions)]
-#[derive(Default)]
-pub struct JsonVisitor {
-    pub fields: serde_json::Map<String, serde_json::Value>,
+pub struct JsonVisitor<'a> {
+    #[cfg_attr(not(all(tracing_unstable, feature = "valuable")), allow(dead_code))]
+    options: &'a LogLayerOptions,
+    pub fields: Fields,
+}
+
+impl<'a> JsonVisitor<'a> {
+    pub const fn new(options: &'a LogLayerOptions) -> Self {
+        Self {
+            options,
+            fields: vec![],
+        }
+    }
 }
 
-impl JsonVisitor {
-    fn filter_insert(&mut self, field: &tracing::field::Field, value: serde_json::Value) {
-        self.fields.insert(field.name().to_string(), value);
+impl JsonVisitor<'_> {
+    /// Captures the value only if the field is allowed, to avoid useless allocations.
+    fn filter_insert(&mut self, field: &tracing::field::Field, value: impl FnOnce() -> FieldValue) {
+        if self.options.field_filter.allows(field.name()) {
+            self.fields.push((field.name(), value()));
+        }
     }
 }
-impl Visit for JsonVisitor {
+impl Visit for JsonVisitor<'_> {
     fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-        // try to parse the string in case it's already a json value
-        let json_value = serde_json::Value::from_str(value).unwrap_or_else(|_| json!(value));
-        self.filter_insert(field, json_value);
+        self.filter_insert(field, || FieldValue::Str(value.to_string()));
     }
     fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
-        self.filter_insert(field, json!(value));
+        self.filter_insert(field, || FieldValue::Bool(value));
     }
     fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
-        self.filter_insert(field, json!(value));
+        self.filter_insert(field, || FieldValue::U64(value));
     }
     fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
-        self.filter_insert(field, json!(value));
+        self.filter_insert(field, || FieldValue::I64(value));
     }
     fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
-        self.filter_insert(field, json!(value));
+        self.filter_insert(field, || FieldValue::F64(value));
+    }
+    fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
+        self.filter_insert(field, || FieldValue::I128(value));
+    }
+    fn record_u128(&mut self, field: &tracing::field::Field, value: u128) {
+        self.filter_insert(field, || FieldValue::U128(value));
+    }
+    fn record_bytes(&mut self, field: &tracing::field::Field, value: &[u8]) {
+        self.filter_insert(field, || FieldValue::Bytes(value.to_vec()));
     }
     fn record_error(
         &mut self,
         field: &tracing::field::Field,
         value: &(dyn std::error::Error + 'static),
     ) {
-        self.filter_insert(field, json!(value.to_string()));
+        self.filter_insert(field, || FieldValue::Error {
+            message: value.to_string(),
+            kind: error_kind(value),
+            chain: std::iter::successors(value.source(), |e| e.source())
+                .map(ToString::to_string)
+                .collect(),
+        });
+    }
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &tracing::field::Field, value: valuable::Value<'_>) {
+        let (limits, large_integers) = (self.options.valuable_limits, self.options.large_integers);
+        self.filter_insert(field, || {
+            FieldValue::Json(crate::valuable::to_json(value, limits, large_integers))
+        });
     }
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
-        self.filter_insert(field, json!(format!("{value:?}")));
+        self.filter_insert(field, || FieldValue::Debug(format!("{value:?}")));
     }
 }
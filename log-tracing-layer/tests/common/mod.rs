@@ -0,0 +1,30 @@
+use async_trait::async_trait;
+use log_tracing_layer::{BatchLogIngestor, FlushReport, IngestError, Log};
+use std::sync::{Arc, Mutex};
+
+/// An ingestor keeping every log in memory so tests can inspect them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryIngestor {
+    pub logs: Arc<Mutex<Vec<Log>>>,
+}
+
+impl MemoryIngestor {
+    pub fn logs(&self) -> Vec<Log> {
+        self.logs.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl BatchLogIngestor for MemoryIngestor {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn ingest_batch(&mut self, mut logs: Vec<Log>) {
+        self.logs.lock().unwrap().append(&mut logs);
+    }
+
+    async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+        Ok(FlushReport::default())
+    }
+}
@@ -0,0 +1,225 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use chrono::DateTime;
+    use log_tracing_layer::{
+        ContextProvider, EcsFormatter, Log, LogLayer, LogLayerOptions, ThreadFields, ECS_VERSION,
+    };
+    use serde_json::{json, Value};
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    /// Field types of the ECS field reference, narrowed to their JSON representation.
+    #[derive(Debug, Clone, Copy)]
+    enum EcsType {
+        /// RFC 3339 string or milliseconds since the epoch.
+        Date,
+        /// `keyword`, `text`, `match_only_text` and `wildcard`.
+        Keyword,
+        Long,
+    }
+
+    /// Fields of the ECS field reference produced by the formatter.
+    const ECS_FIELDS: &[(&str, EcsType)] = &[
+        ("@timestamp", EcsType::Date),
+        ("message", EcsType::Keyword),
+        ("log.level", EcsType::Keyword),
+        ("log.logger", EcsType::Keyword),
+        ("log.origin.file.name", EcsType::Keyword),
+        ("log.origin.file.line", EcsType::Long),
+        ("error.message", EcsType::Keyword),
+        ("error.type", EcsType::Keyword),
+        ("error.stack_trace", EcsType::Keyword),
+        ("trace.id", EcsType::Keyword),
+        ("span.id", EcsType::Keyword),
+        ("service.name", EcsType::Keyword),
+        ("service.version", EcsType::Keyword),
+        ("service.environment", EcsType::Keyword),
+        ("host.hostname", EcsType::Keyword),
+        ("process.pid", EcsType::Long),
+        ("process.name", EcsType::Keyword),
+        ("process.thread.name", EcsType::Keyword),
+        ("process.thread.id", EcsType::Long),
+        ("ecs.version", EcsType::Keyword),
+    ];
+
+    impl EcsType {
+        fn accepts(self, value: &Value) -> bool {
+            match self {
+                Self::Date => value
+                    .as_str()
+                    .map_or(value.is_i64(), |s| DateTime::parse_from_rfc3339(s).is_ok()),
+                Self::Keyword => value.is_string(),
+                Self::Long => value.is_i64(),
+            }
+        }
+    }
+
+    fn leaves<'a>(path: String, value: &'a Value, found: &mut Vec<(String, &'a Value)>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    leaves(format!("{path}.{key}"), value, found);
+                }
+            }
+            value => found.push((path, value)),
+        }
+    }
+
+    /// Checks that every field is either an ECS field of the right type, or one of the custom ones.
+    fn assert_ecs(document: &Log, custom: &[&str]) {
+        let mut found = vec![];
+        for (key, value) in document {
+            leaves(key.clone(), value, &mut found);
+        }
+        for (path, value) in found {
+            let top_level = path.split('.').next().unwrap_or_default();
+            if custom.contains(&top_level) {
+                continue;
+            }
+            let (_, ecs_type) = ECS_FIELDS
+                .iter()
+                .find(|(field, _)| *field == path)
+                .unwrap_or_else(|| panic!("`{path}` is not an ECS field"));
+            assert!(
+                ecs_type.accepts(value),
+                "`{path}` is not a {ecs_type:?}: {value}"
+            );
+        }
+    }
+
+    fn log(value: Value) -> Log {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn layer_logs_are_converted_to_ecs_documents() {
+        let formatter = EcsFormatter::new().with_service_name("checkout");
+        let document = formatter.format(log(json!({
+            "timestamp": "2024-05-01T10:00:00.000000+00:00",
+            "level": "WARN",
+            "target": "checkout::payment",
+            "file": "src/payment.rs",
+            "line": 42,
+            "message": "payment failed",
+            "error": { "message": "card declined", "kind": "PaymentError", "chain": ["insufficient funds"] },
+            "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736",
+            "span_id": "00f067aa0ba902b7",
+            "span": { "name": "pay" },
+            "spans": [{ "name": "checkout" }, { "name": "pay" }],
+            "host": "web-1",
+            "pid": 1234,
+            "executable": "checkout",
+            "version": "1.2.0",
+            "thread.name": "main",
+            "thread.id": 1,
+            "env": "prod",
+            "order_id": 7,
+        })));
+
+        assert_ecs(&document, &["spans", "order_id"]);
+
+        assert_eq!(
+            Value::Object(document),
+            json!({
+                "@timestamp": "2024-05-01T10:00:00.000000+00:00",
+                "message": "payment failed",
+                "log": {
+                    "level": "warn",
+                    "logger": "checkout::payment",
+                    "origin": { "file": { "name": "src/payment.rs", "line": 42 } },
+                },
+                "error": {
+                    "message": "card declined",
+                    "type": "PaymentError",
+                    "stack_trace": "card declined\nCaused by: insufficient funds",
+                },
+                "trace": { "id": "4bf92f3577b34da6a3ce929d0e0e4736" },
+                "span": { "id": "00f067aa0ba902b7" },
+                "service": { "name": "checkout", "version": "1.2.0", "environment": "prod" },
+                "host": { "hostname": "web-1" },
+                "process": { "pid": 1234, "name": "checkout", "thread": { "name": "main", "id": 1 } },
+                "ecs": { "version": ECS_VERSION },
+                "spans": [{ "name": "checkout" }, { "name": "pay" }],
+                "order_id": 7,
+            })
+        );
+    }
+
+    #[test]
+    fn custom_fields_can_be_namespaced() {
+        let formatter = EcsFormatter::new().with_namespace("app");
+        let document = formatter.format(log(json!({
+            "message": "hi",
+            "service": "checkout",
+            "order_id": 7,
+            "log": "custom field clashing with ECS",
+        })));
+
+        assert_ecs(&document, &["app"]);
+        assert_eq!(
+            document["app"],
+            json!({ "order_id": 7, "log": "custom field clashing with ECS" })
+        );
+        assert_eq!(document["service"]["name"], "checkout");
+    }
+
+    #[test]
+    fn the_formatter_can_be_used_as_a_processor() {
+        let ingestor = MemoryIngestor::default();
+        let options = LogLayerOptions::new()
+            .with_context_provider(ContextProvider::Hostname)
+            .with_context_provider(ContextProvider::Pid)
+            .with_context_provider(ContextProvider::Executable)
+            .with_thread_fields(ThreadFields::all())
+            .with_processor(EcsFormatter::new());
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info_span!("request").in_scope(|| tracing::error!(order_id = 7, "boom"));
+        });
+
+        let document = &ingestor.logs()[0];
+        assert_ecs(document, &["spans", "order_id"]);
+        assert!(document["@timestamp"].is_string());
+        assert_eq!(document["log"]["level"], "error");
+        assert_eq!(document["log"]["logger"], module_path!());
+        assert_eq!(document["log"]["origin"]["file"]["name"], file!());
+        assert_eq!(document["trace"]["id"].as_str().unwrap().len(), 32);
+        assert_eq!(document["span"]["id"].as_str().unwrap().len(), 16);
+        assert_eq!(document["ecs"]["version"], ECS_VERSION);
+        assert_eq!(document["order_id"], 7);
+        assert_eq!(document["process"]["pid"], std::process::id());
+        assert!(document["process"]["thread"]["id"].is_u64());
+        for key in [
+            "level",
+            "target",
+            "file",
+            "line",
+            "timestamp",
+            "trace_id",
+            "span_id",
+        ] {
+            assert!(!document.contains_key(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn the_timestamp_is_read_from_the_key_of_the_options() {
+        let ingestor = MemoryIngestor::default();
+        let options = LogLayerOptions::new().with_timestamp_key("ts");
+        let options = options
+            .clone()
+            .with_processor(EcsFormatter::from_options(&options));
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!("hi");
+        });
+
+        let document = &ingestor.logs()[0];
+        assert_ecs(document, &[]);
+        assert!(document["@timestamp"].is_string());
+        assert!(!document.contains_key("ts"));
+    }
+}
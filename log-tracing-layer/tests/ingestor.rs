@@ -0,0 +1,187 @@
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use log_tracing_layer::{
+        BatchLogIngestor, FlushReport, IngestError, IngestorHandle, Log, LogIngestor, LogLayer,
+    };
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+    use std::time::Duration;
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    #[derive(Debug, Clone, Default)]
+    struct Counters {
+        ingested: Arc<AtomicUsize>,
+        flushed: Arc<AtomicUsize>,
+        task_cancelled: Arc<AtomicBool>,
+    }
+
+    #[derive(Debug, Default)]
+    struct Legacy {
+        counters: Counters,
+        pending: usize,
+    }
+
+    #[async_trait]
+    impl LogIngestor for Legacy {
+        fn name(&self) -> &'static str {
+            "legacy"
+        }
+
+        fn start(&self) {}
+
+        async fn ingest(&mut self, _log: Log) {
+            self.pending += 1;
+        }
+
+        async fn flush(&mut self) {
+            self.counters
+                .flushed
+                .fetch_add(self.pending, Ordering::SeqCst);
+            self.pending = 0;
+        }
+    }
+
+    /// Flags `task_cancelled` when the task owned by the handle is aborted.
+    struct CancelGuard(Arc<AtomicBool>);
+
+    impl Drop for CancelGuard {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Batched {
+        counters: Counters,
+        pending: Vec<Log>,
+    }
+
+    #[async_trait]
+    impl BatchLogIngestor for Batched {
+        fn name(&self) -> &'static str {
+            "batched"
+        }
+
+        fn start(&mut self) -> IngestorHandle {
+            let guard = CancelGuard(self.counters.task_cancelled.clone());
+            let mut handle = IngestorHandle::new();
+            handle.push(tokio::spawn(async move {
+                let _guard = guard;
+                std::future::pending::<()>().await;
+            }));
+            handle
+        }
+
+        async fn ingest_batch(&mut self, mut logs: Vec<Log>) {
+            self.counters
+                .ingested
+                .fetch_add(logs.len(), Ordering::SeqCst);
+            self.pending.append(&mut logs);
+        }
+
+        async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+            let sent = self.pending.drain(..).count();
+            self.counters.flushed.fetch_add(sent, Ordering::SeqCst);
+            Ok(FlushReport { sent, dropped: 0 })
+        }
+    }
+
+    fn emit_logs(count: usize) {
+        for i in 0..count {
+            tracing::info!(i, "log");
+        }
+    }
+
+    #[test]
+    fn legacy_ingestors_keep_working() {
+        let counters = Counters::default();
+        let ingestor = Legacy {
+            counters: counters.clone(),
+            pending: 0,
+        };
+        let subscriber = tracing_subscriber::registry().with(LogLayer::new(ingestor));
+        subscriber::with_default(subscriber, || emit_logs(10));
+
+        assert_eq!(counters.flushed.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn batch_ingestors_are_flushed_and_cancelled_on_shutdown() {
+        let counters = Counters::default();
+        let ingestor = Batched {
+            counters: counters.clone(),
+            pending: vec![],
+        };
+        let subscriber =
+            tracing_subscriber::registry().with(LogLayer::from_batch_ingestor(ingestor));
+        subscriber::with_default(subscriber, || emit_logs(10));
+
+        assert_eq!(counters.ingested.load(Ordering::SeqCst), 10);
+        assert_eq!(counters.flushed.load(Ordering::SeqCst), 10);
+        assert!(counters.task_cancelled.load(Ordering::SeqCst));
+    }
+
+    /// Buffers logs until the periodic or the final flush delivers them.
+    #[derive(Debug, Default)]
+    struct Periodic {
+        pending: Vec<Log>,
+        delivered: Arc<Mutex<Vec<u64>>>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl BatchLogIngestor for Periodic {
+        fn name(&self) -> &'static str {
+            "periodic"
+        }
+
+        fn flush_interval(&self) -> Option<Duration> {
+            Some(Duration::from_millis(1))
+        }
+
+        async fn ingest_batch(&mut self, mut logs: Vec<Log>) {
+            self.pending.append(&mut logs);
+        }
+
+        async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+            let logs = std::mem::take(&mut self.pending);
+            let sent = logs.len();
+            // simulate a slow request so flushes overlap with incoming logs
+            tokio::time::sleep(Duration::from_micros(100)).await;
+            self.delivered
+                .lock()
+                .unwrap()
+                .extend(logs.iter().map(|l| l["i"].as_u64().unwrap()));
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(FlushReport { sent, dropped: 0 })
+        }
+    }
+
+    #[test]
+    fn periodic_and_final_flushes_deliver_every_log_once() {
+        let ingestor = Periodic::default();
+        let delivered = ingestor.delivered.clone();
+        let flushes = ingestor.flushes.clone();
+
+        let subscriber =
+            tracing_subscriber::registry().with(LogLayer::from_batch_ingestor(ingestor));
+        subscriber::with_default(subscriber, || {
+            for chunk in 0..20 {
+                for i in 0..50 {
+                    tracing::info!(i = chunk * 50 + i, "log");
+                }
+                std::thread::sleep(Duration::from_millis(2));
+            }
+        });
+
+        let mut delivered = delivered.lock().unwrap().clone();
+        delivered.sort_unstable();
+        assert_eq!(delivered, (0..1000).collect::<Vec<_>>());
+        // the timer did fire before the final flush
+        assert!(flushes.load(Ordering::SeqCst) > 1);
+    }
+}
@@ -0,0 +1,657 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::{
+        crate_version, BytesEncoding, CallsiteFields, ContextProvider, FieldFilter, JsonParsing,
+        LargeIntegers, Log, LogLayer, LogLayerOptions, LoggedError, SecondsFormat, SpanEvents,
+        SpanFormat, ThreadFields, TimestampFormat,
+    };
+    use serde_json::json;
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    fn capture(f: impl FnOnce()) -> Vec<Log> {
+        capture_with(LogLayerOptions::default(), f)
+    }
+
+    fn capture_with(options: LogLayerOptions, f: impl FnOnce()) -> Vec<Log> {
+        let ingestor = MemoryIngestor::default();
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+        ingestor.logs()
+    }
+
+    #[test]
+    fn logs_carry_metadata_fields_and_spans() {
+        let logs = capture(|| {
+            tracing::info_span!("outer", user = "rob").in_scope(|| {
+                tracing::info_span!("inner", attempt = 2).in_scope(|| {
+                    tracing::warn!(ip = "127.0.0.1", retry = true, "first");
+                    tracing::warn!("second");
+                });
+            });
+        });
+
+        assert_eq!(logs.len(), 2);
+        let log = &logs[0];
+        assert_eq!(log["level"], "WARN");
+        assert_eq!(log["target"], module_path!());
+        assert_eq!(log["file"], file!());
+        assert!(log["line"].is_u64());
+        assert!(log["timestamp"].is_string());
+        assert_eq!(log["message"], "first");
+        assert_eq!(log["ip"], "127.0.0.1");
+        assert_eq!(log["retry"], true);
+        assert_eq!(log["span"], json!({ "name": "inner", "attempt": 2 }));
+        assert_eq!(
+            log["spans"],
+            json!([{ "name": "outer", "user": "rob" }, { "name": "inner", "attempt": 2 }])
+        );
+
+        // span fields are shared by every event of the span
+        assert_eq!(logs[1]["spans"], log["spans"]);
+    }
+
+    #[test]
+    fn logs_without_spans_have_no_span_keys() {
+        let logs = capture(|| tracing::info!("no spans"));
+
+        assert!(!logs[0].contains_key("span"));
+        assert!(!logs[0].contains_key("spans"));
+    }
+
+    fn json_fields() {
+        tracing::info!(
+            id = "123",
+            flag = "true",
+            person = r#"{ "name": "rob" }"#,
+            payload.json = r#"{ "items": [1, 2] }"#,
+        );
+    }
+
+    #[test]
+    fn strings_are_not_parsed_by_default() {
+        let logs = capture(json_fields);
+
+        assert_eq!(logs[0]["id"], "123");
+        assert_eq!(logs[0]["flag"], "true");
+        assert_eq!(logs[0]["person"], r#"{ "name": "rob" }"#);
+        assert_eq!(logs[0]["payload.json"], r#"{ "items": [1, 2] }"#);
+    }
+
+    #[test]
+    fn strings_with_suffix_are_parsed() {
+        let options =
+            LogLayerOptions::new().with_json_parsing(JsonParsing::WithSuffix(".json".to_string()));
+        let logs = capture_with(options, json_fields);
+
+        assert_eq!(logs[0]["id"], "123");
+        assert_eq!(logs[0]["person"], r#"{ "name": "rob" }"#);
+        assert_eq!(logs[0]["payload"], json!({ "items": [1, 2] }));
+        assert!(!logs[0].contains_key("payload.json"));
+    }
+
+    #[test]
+    fn every_string_is_parsed_when_asked() {
+        let options = LogLayerOptions::new().with_json_parsing(JsonParsing::Always);
+        let logs = capture_with(options, json_fields);
+
+        assert_eq!(logs[0]["id"], 123);
+        assert_eq!(logs[0]["flag"], true);
+        assert_eq!(logs[0]["person"], json!({ "name": "rob" }));
+    }
+
+    #[derive(Debug)]
+    struct ConfigError {
+        source: std::num::ParseIntError,
+    }
+
+    impl std::fmt::Display for ConfigError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "invalid port")
+        }
+    }
+
+    impl std::error::Error for ConfigError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.source)
+        }
+    }
+
+    #[test]
+    fn errors_keep_their_source_chain() {
+        let error = ConfigError {
+            source: "80a".parse::<u16>().unwrap_err(),
+        };
+        let logs = capture(|| {
+            tracing::error!(error = &error as &dyn std::error::Error, "failed to start");
+        });
+
+        assert_eq!(
+            logs[0]["error"],
+            json!({
+                "message": "invalid port",
+                "kind": "ConfigError",
+                "chain": ["invalid digit found in string"],
+            })
+        );
+
+        let logged = LoggedError::find(&logs[0]).unwrap();
+        assert_eq!(logged.field, "error");
+        assert_eq!(
+            logged.stack(),
+            "invalid port\nCaused by: invalid digit found in string"
+        );
+    }
+
+    /// Error whose `Debug` output doesn't look like a type.
+    struct OpaqueError;
+
+    impl std::fmt::Debug for OpaqueError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "something went wrong")
+        }
+    }
+
+    impl std::fmt::Display for OpaqueError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "opaque")
+        }
+    }
+
+    impl std::error::Error for OpaqueError {}
+
+    #[derive(Debug)]
+    struct StartupError {
+        source: std::io::Error,
+    }
+
+    impl std::fmt::Display for StartupError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "failed to start")
+        }
+    }
+
+    impl std::error::Error for StartupError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.source)
+        }
+    }
+
+    #[test]
+    fn error_kinds_are_detected() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "config.toml not found");
+        let wrapped = StartupError {
+            source: std::io::Error::other(ConfigError {
+                source: "80a".parse::<u16>().unwrap_err(),
+            }),
+        };
+        let logs = capture(|| {
+            tracing::error!(error = &io as &dyn std::error::Error, "io");
+            tracing::error!(error = &OpaqueError as &dyn std::error::Error, "opaque");
+            tracing::error!(error = &wrapped as &dyn std::error::Error, "wrapped");
+        });
+
+        // well-known errors are downcasted
+        assert_eq!(
+            logs[0]["error"],
+            json!({ "message": "config.toml not found", "kind": "std::io::Error", "chain": [] })
+        );
+        // no kind rather than a wrong one
+        assert_eq!(
+            logs[1]["error"],
+            json!({ "message": "opaque", "chain": [] })
+        );
+        // the io error displays the error it wraps, whose source comes next
+        assert_eq!(
+            logs[2]["error"],
+            json!({
+                "message": "failed to start",
+                "kind": "StartupError",
+                "chain": ["invalid port", "invalid digit found in string"],
+            })
+        );
+    }
+
+    fn large_values() {
+        tracing::info!(
+            small = 42u128,
+            unsafe_float = (1u128 << 53) + 1,
+            negative = -(1i128 << 60),
+            huge = u128::MAX,
+            min = i128::MIN,
+            bytes = &b"\x01\xab\xff"[..],
+        );
+    }
+
+    #[test]
+    fn large_integers_outside_the_safe_range_are_strings() {
+        let logs = capture(large_values);
+
+        assert_eq!(logs[0]["small"], 42);
+        assert_eq!(logs[0]["unsafe_float"], "9007199254740993");
+        assert_eq!(logs[0]["negative"], "-1152921504606846976");
+        assert_eq!(logs[0]["huge"], u128::MAX.to_string());
+        assert_eq!(logs[0]["min"], i128::MIN.to_string());
+        assert_eq!(logs[0]["bytes"], "01abff");
+    }
+
+    #[test]
+    fn large_integers_and_bytes_follow_the_options() {
+        let options = LogLayerOptions::new()
+            .with_large_integers(LargeIntegers::Numbers)
+            .with_bytes_encoding(BytesEncoding::Base64);
+        let logs = capture_with(options, large_values);
+
+        assert_eq!(logs[0]["unsafe_float"], (1u64 << 53) + 1);
+        assert_eq!(logs[0]["negative"], -(1i64 << 60));
+        assert_eq!(logs[0]["huge"], u128::MAX.to_string());
+        assert_eq!(logs[0]["min"], i128::MIN.to_string());
+        assert_eq!(logs[0]["bytes"], "Aav/");
+
+        let options = LogLayerOptions::new()
+            .with_large_integers(LargeIntegers::Strings)
+            .with_bytes_encoding(BytesEncoding::Array);
+        let logs = capture_with(options, large_values);
+
+        assert_eq!(logs[0]["small"], "42");
+        assert_eq!(logs[0]["bytes"], json!([1, 171, 255]));
+    }
+
+    #[test]
+    fn dotted_keys_are_expanded_when_enabled() {
+        let options = LogLayerOptions::new().with_expand_dotted_keys(true);
+        let logs = capture_with(options, || {
+            tracing::info_span!("request", usr.id = 7).in_scope(|| {
+                tracing::info!(http.method = "GET", http.status_code = 200, "done");
+            });
+        });
+
+        assert_eq!(
+            logs[0]["http"],
+            json!({ "method": "GET", "status_code": 200 })
+        );
+        assert_eq!(logs[0]["span"]["usr"], json!({ "id": 7 }));
+        assert!(!logs[0].contains_key("http.method"));
+    }
+
+    #[test]
+    fn conflicting_dotted_keys_are_kept_flat() {
+        let options = LogLayerOptions::new().with_expand_dotted_keys(true);
+        let logs = capture_with(options, || {
+            tracing::info!(
+                db = "users",
+                db.name = "orders",
+                http.request.id = 1,
+                http.request = "GET /",
+                "conflicts"
+            );
+        });
+
+        // a leaf is never turned into an object, nor an object into a leaf
+        assert_eq!(logs[0]["db"], "users");
+        assert_eq!(logs[0]["db.name"], "orders");
+        assert_eq!(logs[0]["http"], json!({ "request": { "id": 1 } }));
+        assert_eq!(logs[0]["http.request"], "GET /");
+    }
+
+    #[test]
+    fn a_later_plain_key_moves_dotted_keys_back_to_their_full_name() {
+        let options = LogLayerOptions::new().with_expand_dotted_keys(true);
+        let logs = capture_with(options, || {
+            tracing::info!(http.method = "GET", http.status_code = 200, http = "plain");
+        });
+
+        // no field is dropped
+        assert_eq!(logs[0]["http"], "plain");
+        assert_eq!(logs[0]["http.method"], "GET");
+        assert_eq!(logs[0]["http.status_code"], 200);
+    }
+
+    #[test]
+    fn flattened_span_fields_never_block_event_fields() {
+        let options = LogLayerOptions::new()
+            .with_span_format(SpanFormat::Flattened)
+            .with_expand_dotted_keys(true);
+        let logs = capture_with(options, || {
+            tracing::info_span!(
+                "query",
+                db.name = "orders",
+                db.system = "postgres",
+                http = "GET"
+            )
+            .in_scope(|| {
+                tracing::info!(db = "users", http.method = "POST", "first");
+                tracing::info!(db.user = "rob", "second");
+            });
+        });
+
+        assert_eq!(logs[0]["db"], "users");
+        assert_eq!(logs[0]["http"], json!({ "method": "POST" }));
+        assert!(!logs[0].contains_key("db.name") && !logs[0].contains_key("http.method"));
+        // span fields are merged into the objects of the event
+        assert_eq!(
+            logs[1]["db"],
+            json!({ "name": "orders", "system": "postgres", "user": "rob" })
+        );
+        assert_eq!(logs[1]["http"], "GET");
+    }
+
+    fn nested_spans() {
+        tracing::info_span!("handle_request", user = "rob", attempt = 1).in_scope(|| {
+            tracing::info_span!("db_query", attempt = 2).in_scope(|| {
+                tracing::info!(attempt = 3, "query");
+            });
+        });
+    }
+
+    #[test]
+    fn spans_follow_the_span_format() {
+        let with_format = |format| LogLayerOptions::new().with_span_format(format);
+
+        let log = &capture_with(with_format(SpanFormat::None), nested_spans)[0];
+        assert!(!log.contains_key("span") && !log.contains_key("spans"));
+
+        let log = &capture_with(with_format(SpanFormat::Leaf), nested_spans)[0];
+        assert_eq!(log["span"], json!({ "name": "db_query", "attempt": 2 }));
+        assert!(!log.contains_key("spans"));
+
+        let log = &capture_with(with_format(SpanFormat::List), nested_spans)[0];
+        assert!(!log.contains_key("span"));
+        assert_eq!(log["spans"].as_array().unwrap().len(), 2);
+
+        let log = &capture_with(with_format(SpanFormat::Flattened), nested_spans)[0];
+        assert!(!log.contains_key("span") && !log.contains_key("spans"));
+        assert_eq!(log["user"], "rob");
+        // event fields win over span fields
+        assert_eq!(log["attempt"], 3);
+    }
+
+    #[test]
+    fn leaf_most_span_wins_when_flattened() {
+        let options = LogLayerOptions::new()
+            .with_span_format(SpanFormat::Flattened)
+            .with_span_path(true);
+        let logs = capture_with(options, || {
+            tracing::info_span!("handle_request", attempt = 1).in_scope(|| {
+                tracing::info_span!("db_query", attempt = 2).in_scope(|| tracing::info!("query"));
+            });
+            tracing::info!("outside");
+        });
+
+        assert_eq!(logs[0]["attempt"], 2);
+        assert_eq!(logs[0]["span_path"], "handle_request>db_query");
+        assert!(!logs[1].contains_key("span_path"));
+    }
+
+    #[test]
+    fn span_lifecycle_events_are_logged_when_enabled() {
+        let options = LogLayerOptions::new().with_span_events(SpanEvents::NEW | SpanEvents::CLOSE);
+        let logs = capture_with(options, || {
+            let span = tracing::info_span!("work", job = "sync");
+            span.in_scope(|| std::thread::sleep(std::time::Duration::from_millis(2)));
+            span.in_scope(|| tracing::info!("inside"));
+        });
+
+        let messages: Vec<_> = logs.iter().map(|l| l["message"].clone()).collect();
+        assert_eq!(messages, ["new", "inside", "close"]);
+
+        let close = &logs[2];
+        assert_eq!(close["span"], json!({ "name": "work", "job": "sync" }));
+        assert_eq!(close["level"], "INFO");
+        let duration = close["duration_ns"].as_u64().unwrap();
+        let busy = close["busy_ns"].as_u64().unwrap();
+        let idle = close["idle_ns"].as_u64().unwrap();
+        assert!(busy >= 2_000_000);
+        assert!(busy + idle <= duration);
+        assert!(!logs[0].contains_key("duration_ns"));
+    }
+
+    #[test]
+    fn span_lifecycle_events_are_not_logged_by_default() {
+        let logs = capture(|| {
+            tracing::info_span!("work").in_scope(|| tracing::info!("inside"));
+        });
+
+        assert_eq!(logs.len(), 1);
+    }
+
+    #[test]
+    fn logs_carry_span_and_trace_ids() {
+        let logs = capture(|| {
+            let first = tracing::info_span!("request");
+            let second = tracing::info_span!("request");
+            first.in_scope(|| {
+                tracing::info!("first");
+                tracing::info_span!("db").in_scope(|| tracing::info!("nested"));
+            });
+            second.follows_from(&first);
+            second.in_scope(|| tracing::info!("second"));
+            tracing::info!("outside");
+        });
+
+        let (first, nested, second, outside) = (&logs[0], &logs[1], &logs[2], &logs[3]);
+        assert_eq!(first["trace_id"].as_str().unwrap().len(), 32);
+        assert_eq!(first["span_id"].as_str().unwrap().len(), 16);
+        assert!(!first.contains_key("parent_id"));
+
+        assert_eq!(nested["trace_id"], first["trace_id"]);
+        assert_eq!(nested["parent_id"], first["span_id"]);
+        assert_ne!(nested["span_id"], first["span_id"]);
+
+        assert_ne!(second["trace_id"], first["trace_id"]);
+        assert_ne!(second["span_id"], first["span_id"]);
+        assert_eq!(second["follows_from"], json!([first["span_id"]]));
+
+        assert!(!outside.contains_key("trace_id") && !outside.contains_key("span_id"));
+    }
+
+    #[test]
+    fn context_fields_are_added_to_every_log() {
+        let options = LogLayerOptions::new()
+            .with_context("env", "prod")
+            .with_context("region", "eu-west-1")
+            .with_context_provider(ContextProvider::Pid)
+            .with_context_provider(ContextProvider::Hostname)
+            .with_context_provider(ContextProvider::Executable)
+            .with_context_provider(crate_version!());
+        let logs = capture_with(options, || {
+            tracing::info!("first");
+            tracing::info!(region = "us-east-1", "second");
+        });
+
+        assert_eq!(logs[0]["env"], "prod");
+        assert_eq!(logs[0]["region"], "eu-west-1");
+        assert_eq!(logs[0]["pid"], std::process::id());
+        assert!(logs[0]["host"].is_string());
+        assert!(logs[0]["executable"].as_str().unwrap().starts_with("layer"));
+        assert_eq!(logs[0]["version"], env!("CARGO_PKG_VERSION"));
+        // event fields take precedence
+        assert_eq!(logs[1]["region"], "us-east-1");
+        assert_eq!(logs[1]["env"], "prod");
+    }
+
+    #[test]
+    fn static_context_takes_precedence_over_providers() {
+        let options = LogLayerOptions::new()
+            .with_context_provider(crate_version!())
+            .with_context("version", "1.2.3-custom");
+        let logs = capture_with(options, || tracing::info!("versioned"));
+
+        assert_eq!(logs[0]["version"], "1.2.3-custom");
+    }
+
+    #[test]
+    fn denied_fields_are_dropped() {
+        let options = LogLayerOptions::new().with_field_filter(FieldFilter::deny([
+            "request_id",
+            "http.*",
+            "file",
+            "line",
+        ]));
+        let logs = capture_with(options, || {
+            tracing::info_span!("request", request_id = 42, user = "rob").in_scope(|| {
+                tracing::info!(http.method = "GET", http.url = "/", status = 200, "done");
+            });
+        });
+
+        let log = &logs[0];
+        assert_eq!(log["span"], json!({ "name": "request", "user": "rob" }));
+        assert_eq!(log["status"], 200);
+        assert_eq!(log["message"], "done");
+        assert!(!log.contains_key("http.method") && !log.contains_key("http.url"));
+        assert!(!log.contains_key("file") && !log.contains_key("line"));
+        assert!(log.contains_key("level"));
+    }
+
+    #[test]
+    fn span_event_fields_are_filtered() {
+        let options = LogLayerOptions::new()
+            .with_span_events(SpanEvents::CLOSE)
+            .with_field_filter(FieldFilter::deny(["duration_ns", "message"]));
+        let logs = capture_with(options, || tracing::info_span!("request").in_scope(|| {}));
+
+        assert!(!logs[0].contains_key("duration_ns") && !logs[0].contains_key("message"));
+        assert!(logs[0].contains_key("busy_ns") && logs[0].contains_key("idle_ns"));
+    }
+
+    #[test]
+    fn only_allowed_fields_are_kept() {
+        let options = LogLayerOptions::new()
+            .with_field_filter(FieldFilter::allow(["message", "level", "usr.*"]));
+        let logs = capture_with(options, || {
+            tracing::info_span!("request", usr.id = 7).in_scope(|| {
+                tracing::warn!(usr.name = "rob", ip = "127.0.0.1", "login");
+            });
+        });
+
+        let keys: Vec<&str> = logs[0].keys().map(String::as_str).collect();
+        assert_eq!(keys, ["level", "message", "usr.name"]);
+    }
+
+    #[test]
+    fn timestamps_follow_the_configured_format() {
+        let format = |timestamp_format: TimestampFormat| {
+            let options = LogLayerOptions::new().with_timestamp_format(timestamp_format);
+            capture_with(options, || tracing::info!("hi"))[0]["timestamp"].clone()
+        };
+
+        let rfc3339 = format(TimestampFormat::Rfc3339 {
+            precision: SecondsFormat::Millis,
+            use_z: true,
+        });
+        let rfc3339 = rfc3339.as_str().unwrap();
+        // e.g. 2024-05-01T10:00:00.123Z
+        assert_eq!(rfc3339.len(), 24, "{rfc3339}");
+        assert!(rfc3339.ends_with('Z'));
+
+        let seconds = format(TimestampFormat::EpochSeconds).as_i64().unwrap();
+        let millis = format(TimestampFormat::EpochMillis).as_i64().unwrap();
+        let micros = format(TimestampFormat::EpochMicros).as_i64().unwrap();
+        let nanos = format(TimestampFormat::EpochNanos).as_i64().unwrap();
+        assert!(seconds > 1_700_000_000);
+        assert!((millis / 1000 - seconds).abs() <= 1);
+        assert!((micros / 1000 - millis).abs() <= 1000);
+        assert!((nanos / 1000 - micros).abs() <= 1_000_000);
+
+        let custom = format(TimestampFormat::Custom("%Y/%m/%d".to_string()));
+        assert_eq!(custom.as_str().unwrap().len(), 10);
+        assert_eq!(custom.as_str().unwrap().matches('/').count(), 2);
+    }
+
+    #[test]
+    fn the_timestamp_key_can_be_renamed() {
+        let options = LogLayerOptions::new()
+            .with_timestamp_key("@timestamp")
+            .with_field_filter(FieldFilter::deny(["timestamp"]));
+        let logs = capture_with(options, || tracing::info!("hi"));
+
+        assert!(logs[0]["@timestamp"].is_string());
+        assert!(!logs[0].contains_key("timestamp"));
+
+        let options = LogLayerOptions::new()
+            .with_timestamp_key("@timestamp")
+            .with_field_filter(FieldFilter::deny(["@timestamp"]));
+        let logs = capture_with(options, || tracing::info!("hi"));
+        assert!(!logs[0].contains_key("@timestamp"));
+    }
+
+    #[test]
+    fn thread_and_task_are_added_when_enabled() {
+        let options = LogLayerOptions::new().with_thread_fields(ThreadFields::all());
+        let logs = std::thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(|| {
+                capture_with(options, || {
+                    tracing::info!("outside a task");
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .build()
+                        .unwrap();
+                    runtime.block_on(async {
+                        tokio::spawn(async { tracing::info!("inside a task") })
+                            .await
+                            .unwrap();
+                    });
+                })
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(logs[0]["thread.name"], "worker");
+        assert!(logs[0]["thread.id"].is_u64());
+        assert!(!logs[0].contains_key("task.id"));
+        assert_eq!(logs[1]["thread.id"], logs[0]["thread.id"]);
+        assert!(logs[1]["task.id"].is_u64());
+
+        let logs = capture(|| tracing::info!("hi"));
+        assert!(!logs[0].contains_key("thread.name") && !logs[0].contains_key("thread.id"));
+    }
+
+    #[test]
+    fn thread_fields_are_flat_and_filtered() {
+        let options = LogLayerOptions::new()
+            .with_thread_fields(ThreadFields {
+                name: true,
+                id: true,
+                task_id: false,
+            })
+            .with_expand_dotted_keys(true)
+            .with_field_filter(FieldFilter::deny(["thread.id"]));
+        let logs = capture_with(options, || tracing::info!("hi"));
+
+        assert!(logs[0]["thread.name"].is_string());
+        assert!(!logs[0].contains_key("thread.id"));
+        assert!(!logs[0].contains_key("thread"));
+    }
+
+    #[test]
+    fn callsite_metadata_is_added_when_enabled() {
+        let options = LogLayerOptions::new().with_callsite_fields(CallsiteFields::all());
+        let logs = capture_with(options, || {
+            for user in ["rob", "ana"] {
+                tracing::info!(user, "login from {user}");
+            }
+            tracing::info!(user = "rob", "logout");
+        });
+
+        assert_eq!(logs[0]["module_path"], module_path!());
+        assert!(logs[0]["event.name"]
+            .as_str()
+            .unwrap()
+            .starts_with("event "));
+        let fingerprint = logs[0]["fingerprint"].as_str().unwrap();
+        assert_eq!(fingerprint.len(), 16);
+        assert!(fingerprint.chars().all(|c| c.is_ascii_hexdigit()));
+        // the same statement with different values
+        assert_eq!(logs[1]["fingerprint"], logs[0]["fingerprint"]);
+        assert_ne!(logs[2]["fingerprint"], logs[0]["fingerprint"]);
+
+        let logs = capture(|| tracing::info!("hi"));
+        for key in ["module_path", "event.name", "fingerprint"] {
+            assert!(!logs[0].contains_key(key), "{key}");
+        }
+    }
+}
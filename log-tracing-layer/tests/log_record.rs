@@ -0,0 +1,158 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::{
+        Log, LogLayer, LogLayerOptions, LogRecord, LogRecordFormatter, TimestampFormat,
+    };
+    use serde_json::{json, Value};
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    fn log(value: Value) -> Log {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn layer_logs_are_converted_to_log_records() {
+        let options = LogLayerOptions::new()
+            .with_context("service", "checkout")
+            .with_context("env", "prod");
+        let formatter = LogRecordFormatter::from_options(&options);
+        let record = formatter.format(log(json!({
+            "timestamp": "2024-05-01T10:00:00.000000123+00:00",
+            "level": "WARN",
+            "target": "checkout::payment",
+            "file": "src/payment.rs",
+            "line": 42,
+            "message": "payment failed",
+            "error": { "message": "card declined", "kind": "PaymentError", "chain": [] },
+            "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736",
+            "span_id": "00f067aa0ba902b7",
+            "service": "checkout",
+            "env": "prod",
+            "order_id": 7,
+        })));
+
+        assert_eq!(record.time_unix_nano, 1_714_557_600_000_000_123);
+        assert!(record.observed_time_unix_nano > record.time_unix_nano);
+        assert_eq!(record.severity_number, 13);
+        assert_eq!(record.severity_text, "WARN");
+        assert_eq!(record.body, json!("payment failed"));
+        assert_eq!(
+            record.trace_id.as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(record.span_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_eq!(
+            Value::Object(record.resource),
+            json!({ "service.name": "checkout", "deployment.environment.name": "prod" })
+        );
+        assert_eq!(
+            Value::Object(record.attributes),
+            json!({
+                "code.namespace": "checkout::payment",
+                "code.filepath": "src/payment.rs",
+                "code.lineno": 42,
+                "exception.message": "card declined",
+                "exception.type": "PaymentError",
+                "exception.stacktrace": "card declined",
+                "order_id": 7,
+            })
+        );
+    }
+
+    #[test]
+    fn timestamps_are_read_in_the_format_of_the_layer() {
+        let options = LogLayerOptions::new()
+            .with_timestamp_format(TimestampFormat::EpochMillis)
+            .with_timestamp_key("ts");
+        let formatter = LogRecordFormatter::from_options(&options);
+        let record = formatter.format(log(json!({ "ts": 1_714_557_600_123_i64, "message": "hi" })));
+
+        assert_eq!(record.time_unix_nano, 1_714_557_600_123_000_000);
+        assert!(record.attributes.is_empty());
+
+        // nanoseconds that don't fit in an i64 are written as strings
+        let formatter =
+            LogRecordFormatter::new().with_timestamp_format(TimestampFormat::EpochNanos);
+        let record = formatter.format(log(json!({ "timestamp": "1714557600123456789" })));
+
+        assert_eq!(record.time_unix_nano, 1_714_557_600_123_456_789);
+    }
+
+    #[test]
+    fn levels_are_mapped_to_severity_numbers() {
+        use tracing::Level;
+        let numbers: Vec<u8> = [
+            Level::TRACE,
+            Level::DEBUG,
+            Level::INFO,
+            Level::WARN,
+            Level::ERROR,
+        ]
+        .into_iter()
+        .map(LogRecord::severity_number)
+        .collect();
+        assert_eq!(numbers, [1, 5, 9, 13, 17]);
+    }
+
+    #[test]
+    fn records_are_encoded_as_otlp_json() {
+        let ingestor = MemoryIngestor::default();
+        let options = LogLayerOptions::new().with_context("service", "checkout");
+        let formatter = LogRecordFormatter::from_options(&options);
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info!(retries = 2, ok = true, ratio = 0.5, "first");
+                tracing::error!("second");
+            });
+        });
+
+        let records: Vec<LogRecord> = ingestor
+            .logs()
+            .into_iter()
+            .map(|log| formatter.format(log))
+            .collect();
+        let request = LogRecord::export_request(&records);
+
+        let resource_logs = request["resourceLogs"].as_array().unwrap();
+        assert_eq!(resource_logs.len(), 1);
+        assert_eq!(
+            resource_logs[0]["resource"]["attributes"],
+            json!([{ "key": "service.name", "value": { "stringValue": "checkout" } }])
+        );
+        let scope_logs = &resource_logs[0]["scopeLogs"][0];
+        assert_eq!(scope_logs["scope"]["name"], "log-tracing-layer");
+
+        let first = &scope_logs["logRecords"][0];
+        assert_eq!(first["severityNumber"], 9);
+        assert_eq!(first["severityText"], "INFO");
+        assert_eq!(first["body"], json!({ "stringValue": "first" }));
+        assert!(first["timeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse::<u64>()
+            .is_ok());
+        assert_eq!(first["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(first["spanId"].as_str().unwrap().len(), 16);
+        let attribute = |key: &str| {
+            first["attributes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|kv| kv["key"] == key)
+                .map(|kv| kv["value"].clone())
+        };
+        assert_eq!(attribute("retries"), Some(json!({ "intValue": "2" })));
+        assert_eq!(attribute("ok"), Some(json!({ "boolValue": true })));
+        assert_eq!(attribute("ratio"), Some(json!({ "doubleValue": 0.5 })));
+        assert_eq!(
+            attribute("code.namespace"),
+            Some(json!({ "stringValue": module_path!() }))
+        );
+        assert_eq!(scope_logs["logRecords"][1]["severityNumber"], 17);
+    }
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "opentelemetry")]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::LogLayer;
+    use opentelemetry::trace::{TraceContextExt, TracerProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing::subscriber;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn logs_carry_the_opentelemetry_ids() {
+        let provider = SdkTracerProvider::builder().build();
+        let ingestor = MemoryIngestor::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+            .with(LogLayer::from_batch_ingestor(ingestor.clone()));
+
+        let mut expected = vec![];
+        subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request");
+            span.in_scope(|| tracing::info!("inside"));
+            let context = span.context();
+            let span_context = context.span().span_context().clone();
+            expected.push(format!("{:032x}", span_context.trace_id()));
+            expected.push(format!("{:016x}", span_context.span_id()));
+            tracing::info!("outside");
+        });
+
+        let logs = ingestor.logs();
+        assert_eq!(logs[0]["trace.id"], expected[0]);
+        assert_eq!(logs[0]["span.id"], expected[1]);
+        assert!(!logs[1].contains_key("trace.id"));
+    }
+}
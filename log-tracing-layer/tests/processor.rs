@@ -0,0 +1,69 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::{Decision, Log, LogLayer, LogLayerOptions, LogProcessor};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    /// Keeps one log out of `n`.
+    struct Sampler {
+        n: usize,
+        seen: AtomicUsize,
+    }
+
+    impl LogProcessor for Sampler {
+        fn process(&self, _log: &mut Log) -> Decision {
+            if self
+                .seen
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(self.n)
+            {
+                Decision::Keep
+            } else {
+                Decision::Drop
+            }
+        }
+    }
+
+    #[test]
+    fn processors_run_in_order_and_can_drop_logs() {
+        let options = LogLayerOptions::new()
+            .with_processor(|log: &mut Log| {
+                if log["level"] == "DEBUG" {
+                    return Decision::Drop;
+                }
+                log.insert("team".to_string(), json!("payments"));
+                Decision::Keep
+            })
+            .with_processor(Sampler {
+                n: 2,
+                seen: AtomicUsize::new(0),
+            })
+            .with_processor(|log: &mut Log| {
+                // sees what the previous processors did
+                let team = log.remove("team").unwrap();
+                log.insert("owner".to_string(), team);
+                Decision::Keep
+            });
+
+        let ingestor = MemoryIngestor::default();
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::debug!("dropped");
+            for i in 0..4 {
+                tracing::info!(i, "sampled");
+            }
+        });
+
+        let logs = ingestor.logs();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0]["i"], 0);
+        assert_eq!(logs[1]["i"], 2);
+        assert_eq!(logs[0]["owner"], "payments");
+        assert!(!logs[0].contains_key("team"));
+    }
+}
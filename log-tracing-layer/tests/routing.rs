@@ -0,0 +1,179 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use async_trait::async_trait;
+    use log_tracing_layer::{
+        BatchLogIngestor, FlushReport, IngestError, Log, LogLayer, LogLayerOptions, RouteFilter,
+        Router, Schema,
+    };
+    use std::sync::{Arc, Mutex};
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    fn messages(ingestor: &MemoryIngestor) -> Vec<String> {
+        ingestor
+            .logs()
+            .iter()
+            .map(|l| l["message"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn routes_logs_to_matching_ingestors() {
+        let warnings = MemoryIngestor::default();
+        let audit = MemoryIngestor::default();
+        let db = MemoryIngestor::default();
+
+        let router = Router::new()
+            .route("warnings", "warn".parse().unwrap(), warnings.clone())
+            .route("audit", "audit".parse().unwrap(), audit.clone())
+            .route(
+                "db",
+                "[query{table=users}]=debug".parse().unwrap(),
+                db.clone(),
+            );
+
+        let subscriber = tracing_subscriber::registry().with(LogLayer::from_batch_ingestor(router));
+        subscriber::with_default(subscriber, || {
+            tracing::info!("info");
+            tracing::warn!("warn");
+            tracing::trace!(target: "audit", "audit");
+            tracing::error!(target: "audit", "audit error");
+            tracing::debug_span!("query", table = "users").in_scope(|| {
+                tracing::debug!("users query");
+            });
+            tracing::debug_span!("query", table = "orders").in_scope(|| {
+                tracing::debug!("orders query");
+            });
+        });
+
+        assert_eq!(messages(&warnings), ["warn", "audit error"]);
+        assert_eq!(messages(&audit), ["audit", "audit error"]);
+        assert_eq!(messages(&db), ["users query"]);
+    }
+
+    #[test]
+    fn logs_are_routed_before_the_schema_is_applied() {
+        let warnings = MemoryIngestor::default();
+        let nested = MemoryIngestor::default();
+
+        let router = Router::new()
+            .route("warnings", "warn".parse().unwrap(), warnings.clone())
+            .route(
+                "nested",
+                "app".parse().unwrap(),
+                Router::new().route("db", "app::db=debug".parse().unwrap(), nested.clone()),
+            );
+        let options = LogLayerOptions::new().with_schema(Schema::datadog());
+        let subscriber =
+            tracing_subscriber::registry().with(LogLayer::with_options(router, options));
+        subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app::db", "query");
+            tracing::warn!(target: "app::db", "slow query");
+        });
+
+        assert_eq!(messages(&warnings), ["slow query"]);
+        assert_eq!(messages(&nested), ["query", "slow query"]);
+        // the schema is applied only once, even by nested routers
+        for log in warnings.logs().iter().chain(&nested.logs()) {
+            assert!(log["status"].is_string());
+            assert_eq!(log["logger"]["name"], "app::db");
+            assert!(!log.contains_key("level") && !log.contains_key("target"));
+        }
+    }
+
+    #[test]
+    fn most_specific_directive_wins() {
+        let filter: RouteFilter = "warn,noisy=off,app::db=debug".parse().unwrap();
+        let log = |level: &str, target: &str| {
+            serde_json::json!({ "level": level, "target": target })
+                .as_object()
+                .unwrap()
+                .clone()
+        };
+
+        assert!(filter.matches(&log("ERROR", "app")));
+        assert!(!filter.matches(&log("INFO", "app")));
+        assert!(!filter.matches(&log("ERROR", "noisy::module")));
+        assert!(filter.matches(&log("DEBUG", "app::db::pool")));
+        assert!(!filter.matches(&log("TRACE", "app::db")));
+    }
+
+    #[test]
+    fn span_fields_match_expanded_dotted_keys() {
+        let filter: RouteFilter = "[request{usr.id=7}]".parse().unwrap();
+        let log = |span: serde_json::Value| {
+            serde_json::json!({ "level": "INFO", "target": "app", "spans": [span] })
+                .as_object()
+                .unwrap()
+                .clone()
+        };
+
+        assert!(filter.matches(&log(serde_json::json!({ "name": "request", "usr.id": 7 }))));
+        assert!(filter.matches(&log(
+            serde_json::json!({ "name": "request", "usr": { "id": 7 } })
+        )));
+        assert!(!filter.matches(&log(
+            serde_json::json!({ "name": "request", "usr": { "id": 8 } })
+        )));
+    }
+
+    #[test]
+    fn invalid_directives_are_rejected() {
+        assert!("app=loud".parse::<RouteFilter>().is_err());
+        assert!("app[span{field}".parse::<RouteFilter>().is_err());
+        assert!("[span{field]".parse::<RouteFilter>().is_err());
+    }
+
+    /// Only delivers its logs when it's shut down, like ingestors sending a last batch.
+    #[derive(Debug, Default)]
+    struct ShutdownOnly {
+        pending: Vec<Log>,
+        delivered: Arc<Mutex<Vec<Log>>>,
+    }
+
+    #[async_trait]
+    impl BatchLogIngestor for ShutdownOnly {
+        fn name(&self) -> &'static str {
+            "shutdown-only"
+        }
+
+        async fn ingest_batch(&mut self, mut logs: Vec<Log>) {
+            self.pending.append(&mut logs);
+        }
+
+        async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+            Ok(FlushReport::default())
+        }
+
+        async fn shutdown(&mut self) -> Result<FlushReport, IngestError> {
+            let sent = self.pending.len();
+            self.delivered.lock().unwrap().append(&mut self.pending);
+            Ok(FlushReport { sent, dropped: 0 })
+        }
+    }
+
+    #[test]
+    fn routes_are_shut_down_with_the_layer() {
+        let ingestor = ShutdownOnly::default();
+        let delivered = ingestor.delivered.clone();
+        let router = Router::new().route("last-batch", "info".parse().unwrap(), ingestor);
+
+        let subscriber = tracing_subscriber::registry().with(LogLayer::from_batch_ingestor(router));
+        let guard = subscriber::set_default(subscriber);
+        tracing::info!("first");
+        tracing::warn!("second");
+        // dropping the subscriber drops the layer, which waits for the shutdown
+        drop(guard);
+
+        let messages: Vec<String> = delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|l| l["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(messages, ["first", "second"]);
+    }
+}
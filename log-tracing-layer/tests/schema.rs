@@ -0,0 +1,61 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::{Log, LogLayer, LogLayerOptions, Schema};
+    use serde_json::json;
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    fn capture_with(schema: Schema) -> Log {
+        let ingestor = MemoryIngestor::default();
+        let options = LogLayerOptions::new().with_schema(schema);
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::warn!(target: "app::db", "slow query");
+        });
+        ingestor.logs().remove(0)
+    }
+
+    #[test]
+    fn keys_are_renamed_or_nested() {
+        let log = capture_with(
+            Schema::new()
+                .rename("level", "status")
+                .rename("target", "logger.name")
+                .nest("file", "log.origin.file.name")
+                .nest("line", "log.origin.file.line"),
+        );
+
+        assert_eq!(log["status"], "WARN");
+        assert_eq!(log["logger.name"], "app::db");
+        assert_eq!(log["log"]["origin"]["file"]["name"], file!());
+        assert!(log["log"]["origin"]["file"]["line"].is_u64());
+        for key in ["level", "target", "file", "line"] {
+            assert!(!log.contains_key(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn presets_follow_their_conventions() {
+        let log = capture_with(Schema::datadog());
+        assert_eq!(log["status"], "WARN");
+        assert_eq!(log["logger"], json!({ "name": "app::db" }));
+        assert!(log["date"].is_string());
+
+        let log = capture_with(Schema::ecs());
+        assert!(log["@timestamp"].is_string());
+        assert_eq!(log["log"]["level"], "WARN");
+        assert_eq!(log["log"]["logger"], "app::db");
+        assert_eq!(log["log"]["origin"]["file"]["name"], file!());
+        assert!(log["log"]["origin"]["file"]["line"].is_u64());
+        assert_eq!(log["message"], "slow query");
+
+        let log = capture_with(Schema::opentelemetry());
+        assert_eq!(log["severity_text"], "WARN");
+        assert_eq!(log["body"], "slow query");
+        assert_eq!(log["code.namespace"], "app::db");
+        assert_eq!(log["code.filepath"], file!());
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::{LogLayer, TraceParent};
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn traceparents_are_parsed_and_formatted() {
+        let parent = TraceParent::parse(TRACEPARENT).unwrap();
+
+        assert_eq!(parent.trace_id, 0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736);
+        assert_eq!(parent.parent_id, 0x00f0_67aa_0ba9_02b7);
+        assert!(parent.is_sampled());
+        assert_eq!(parent.to_string(), TRACEPARENT);
+
+        // future versions may have more parts
+        assert!(TraceParent::parse(&format!("01{}-extra", &TRACEPARENT[2..])).is_ok());
+    }
+
+    #[test]
+    fn invalid_traceparents_are_rejected() {
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert!(TraceParent::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn logs_inside_a_span_with_a_traceparent_continue_the_trace() {
+        let ingestor = MemoryIngestor::default();
+        let layer = LogLayer::from_batch_ingestor(ingestor.clone());
+        subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let parent = TraceParent::parse(TRACEPARENT)
+                .unwrap()
+                .with_tracestate("congo=t61rcWkgMzE");
+            tracing::info_span!("request", traceparent = %parent, tracestate = parent.tracestate)
+                .in_scope(|| {
+                    tracing::info_span!("db").in_scope(|| tracing::info!("query"));
+                });
+            tracing::info_span!("request", traceparent = "invalid").in_scope(|| {
+                tracing::info!("no remote parent");
+            });
+        });
+
+        let logs = ingestor.logs();
+        assert_eq!(logs[0]["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(logs[0]["parent_span_id"], "00f067aa0ba902b7");
+        assert_eq!(logs[0]["trace.id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(logs[0]["span.id"], "00f067aa0ba902b7");
+        assert_eq!(logs[0]["tracestate"], "congo=t61rcWkgMzE");
+
+        assert_ne!(logs[1]["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(!logs[1].contains_key("parent_span_id"));
+        assert!(!logs[1].contains_key("trace.id"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Requires `RUSTFLAGS="--cfg tracing_unstable"` and the `valuable` feature.
+#![cfg(all(tracing_unstable, feature = "valuable"))]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::MemoryIngestor;
+    use log_tracing_layer::{Log, LogLayer, LogLayerOptions, ValuableLimits};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use tracing::subscriber;
+    use tracing_subscriber::prelude::*;
+    use valuable::{
+        Fields, NamedField, NamedValues, StructDef, Structable, Valuable, Value, Visit,
+    };
+
+    struct User {
+        name: &'static str,
+        roles: Vec<&'static str>,
+        quota: Option<u128>,
+    }
+
+    static USER_FIELDS: &[NamedField<'static>] = &[
+        NamedField::new("name"),
+        NamedField::new("roles"),
+        NamedField::new("quota"),
+    ];
+
+    impl Valuable for User {
+        fn as_value(&self) -> Value<'_> {
+            Value::Structable(self)
+        }
+
+        fn visit(&self, visit: &mut dyn Visit) {
+            visit.visit_named_fields(&NamedValues::new(
+                USER_FIELDS,
+                &[
+                    self.name.as_value(),
+                    self.roles.as_value(),
+                    self.quota.as_value(),
+                ],
+            ));
+        }
+    }
+
+    impl Structable for User {
+        fn definition(&self) -> StructDef<'_> {
+            StructDef::new_static("User", Fields::Named(USER_FIELDS))
+        }
+    }
+
+    /// Struct without fields, like `struct Empty {}`.
+    struct Empty;
+
+    impl Valuable for Empty {
+        fn as_value(&self) -> Value<'_> {
+            Value::Structable(self)
+        }
+
+        fn visit(&self, visit: &mut dyn Visit) {
+            visit.visit_named_fields(&NamedValues::new(&[], &[]));
+        }
+    }
+
+    impl Structable for Empty {
+        fn definition(&self) -> StructDef<'_> {
+            StructDef::new_static("Empty", Fields::Named(&[]))
+        }
+    }
+
+    fn capture_with(options: LogLayerOptions, f: impl FnOnce()) -> Vec<Log> {
+        let ingestor = MemoryIngestor::default();
+        let layer = LogLayer::with_options(ingestor.clone(), options);
+        subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+        ingestor.logs()
+    }
+
+    #[test]
+    fn valuable_fields_are_nested_objects() {
+        let user = User {
+            name: "rob",
+            roles: vec!["admin", "dev"],
+            quota: Some(u128::MAX),
+        };
+        let scores = BTreeMap::from([("a", 1), ("b", 2)]);
+        let logs = capture_with(LogLayerOptions::default(), || {
+            tracing::info_span!("request", user = user.as_value()).in_scope(|| {
+                tracing::info!(scores = scores.as_value(), pair = (1, "x").as_value(), "hi");
+            });
+        });
+
+        let expected_user = json!({
+            "name": "rob",
+            "roles": ["admin", "dev"],
+            "quota": u128::MAX.to_string(),
+        });
+        assert_eq!(logs[0]["span"]["user"], expected_user);
+        assert_eq!(logs[0]["scores"], json!({ "a": 1, "b": 2 }));
+        assert_eq!(logs[0]["pair"], json!([1, "x"]));
+    }
+
+    #[test]
+    fn valuable_fields_are_truncated() {
+        let nested = vec![vec![vec![1, 2, 3]]];
+        let options = LogLayerOptions::new().with_valuable_limits(ValuableLimits {
+            max_depth: 2,
+            max_len: 2,
+        });
+        let logs = capture_with(options, || {
+            tracing::info!(nested = nested.as_value(), list = [1, 2, 3].as_value());
+        });
+
+        assert_eq!(logs[0]["nested"], json!([["..."]]));
+        assert_eq!(logs[0]["list"], json!([1, 2]));
+    }
+
+    #[test]
+    fn empty_valuable_fields_keep_their_kind() {
+        let list: Vec<u8> = vec![];
+        let map: BTreeMap<&str, u8> = BTreeMap::new();
+        let logs = capture_with(LogLayerOptions::default(), || {
+            tracing::info!(
+                list = list.as_value(),
+                map = map.as_value(),
+                empty = Empty.as_value(),
+            );
+        });
+
+        assert_eq!(logs[0]["list"], json!([]));
+        assert_eq!(logs[0]["map"], json!({}));
+        assert_eq!(logs[0]["empty"], json!({}));
+    }
+}
@@ -12,25 +12,34 @@
 //!   tracing::info!("your message: {}", msg);
 //! }
 //!
-//! fn main() {
-//!     let options = NewRelicOptions::new("my-service", "my-new-relic-api-key")
-//!         .with_tags("env:dev");
-//!     let dd = nr_tracing_layer::create(options);
-//!     let subscriber = tracing_subscriber::registry()
-//!         .with(tracing_subscriber::fmt::Layer::new().json())
-//!         .with(dd);
-//!     let _s = subscriber::set_default(subscriber);
-//!     log("hello world!");
-//! }
+//! let options = NewRelicOptions::new("my-service", "my-new-relic-api-key")
+//!     .with_tags("env:dev");
+//! let dd = nr_tracing_layer::create(options);
+//! let subscriber = tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::Layer::new().json())
+//!     .with(dd);
+//! let _s = subscriber::set_default(subscriber);
+//! log("hello world!");
 //!```
 mod new_relic_ingestor;
 
-pub use log_tracing_layer::LogLayer;
-pub use new_relic_ingestor::{NewRelicOptions, Region};
+pub use log_tracing_layer::{
+    crate_version, BytesEncoding, CallsiteFields, ContextProvider, Decision, EcsFormatter,
+    FieldFilter, JsonParsing, LargeIntegers, Log, LogLayer, LogLayerOptions, LogProcessor, Schema,
+    SecondsFormat, SpanEvents, SpanFormat, ThreadFields, TimestampFormat,
+};
+#[cfg(feature = "redaction")]
+pub use log_tracing_layer::{Pattern, RedactAction, Redactor};
+pub use new_relic_ingestor::{NewRelicLogIngestor, NewRelicOptions, Region};
 
 /// Creates a log layer that will send logs to New Relic.
 #[must_use]
-pub fn create(options: NewRelicOptions) -> LogLayer {
+pub fn create(mut options: NewRelicOptions) -> LogLayer {
+    let mut layer_options = std::mem::take(&mut options.layer);
+    // New Relic expects the timestamp in milliseconds since the epoch
+    layer_options
+        .timestamp_format
+        .get_or_insert(TimestampFormat::EpochMillis);
     let ingestor = new_relic_ingestor::NewRelicLogIngestor::new(options);
-    LogLayer::new(ingestor)
+    LogLayer::with_options(ingestor, layer_options)
 }
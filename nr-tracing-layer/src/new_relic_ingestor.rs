@@ -1,10 +1,10 @@
 use async_recursion::async_recursion;
 use async_trait::async_trait;
-use chrono::Utc;
-use log_tracing_layer::{Log, LogEvent, LogIngestor};
+use log_tracing_layer::{
+    BatchLogIngestor, FlushReport, IngestError, Log, LogLayerOptions, LoggedError,
+};
 use serde_json::json;
-use std::{collections::VecDeque, error::Error, io::Write, sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use std::{collections::VecDeque, error::Error, io::Write, time::Duration};
 
 #[cfg(not(feature = "tls"))]
 use reqwest_default as reqwest;
@@ -14,7 +14,7 @@ use reqwest_tls as reqwest;
 
 const NR_SOURCE: &str = "nr-tracing-layer";
 const MAX_BATCH_SIZE: usize = 1000;
-const MAX_BATCH_DURATION_SECS: i64 = 5;
+const MAX_BATCH_DURATION_SECS: u64 = 5;
 const MAX_RETRIES: u8 = 3;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,6 +31,7 @@ pub struct NewRelicOptions {
     pub region: Option<Region>,
     pub url: Option<String>,
     pub tags: Option<String>,
+    pub layer: LogLayerOptions,
 }
 
 impl NewRelicOptions {
@@ -62,6 +63,30 @@ impl NewRelicOptions {
         self.url = Some(url.into());
         self
     }
+
+    /// Options of the underlying [`log_tracing_layer::LogLayer`].
+    #[must_use]
+    pub fn with_layer_options(mut self, layer: LogLayerOptions) -> Self {
+        self.layer = layer;
+        self
+    }
+}
+
+/// Fills the `error.message`, `error.class` and `error.stack` attributes.
+fn add_error_attributes(log: &mut Log) {
+    let Some(error) = LoggedError::find(log) else {
+        return;
+    };
+    let mut attributes = vec![
+        ("error.message", json!(error.message)),
+        ("error.stack", json!(error.stack())),
+    ];
+    if let Some(kind) = error.kind {
+        attributes.push(("error.class", json!(kind)));
+    }
+    for (key, value) in attributes {
+        log.entry(key).or_insert(value);
+    }
 }
 
 #[derive(Debug, Default)]
@@ -71,7 +96,7 @@ pub struct NewRelicLogIngestor {
     api_key: String,
     tags: String,
     client: reqwest::Client,
-    queue: Arc<RwLock<VecDeque<LogEvent>>>,
+    queue: VecDeque<Log>,
 }
 
 impl NewRelicLogIngestor {
@@ -100,7 +125,7 @@ impl NewRelicLogIngestor {
             api_key: options.api_key,
             tags,
             client: reqwest::Client::new(),
-            queue: Arc::new(RwLock::new(VecDeque::new())),
+            queue: VecDeque::new(),
         }
     }
 
@@ -113,27 +138,28 @@ impl NewRelicLogIngestor {
     }
 
     #[async_recursion]
-    async fn send_logs(&self, logs: &[Log], retries: u8) {
+    async fn send_logs(&self, logs: &[Log], retries: u8) -> Result<FlushReport, IngestError> {
+        let dropped = FlushReport {
+            sent: 0,
+            dropped: logs.len(),
+        };
+
         if retries > MAX_RETRIES {
             eprintln!("Failed to send logs after {} retries", retries);
-            return;
+            return Ok(dropped);
         }
 
         let retry = || async {
             let next = retries + 1;
             let next_time = 100 * next as u64;
             tokio::time::sleep(Duration::from_millis(next_time)).await;
-            self.send_logs(logs, next).await;
+            self.send_logs(logs, next).await
         };
 
         // compress the logs
-        let compressed_logs = match self.compress(logs) {
-            Ok(logs) => logs,
-            Err(e) => {
-                eprintln!("Failed to compress logs: {:?}", e);
-                return;
-            }
-        };
+        let compressed_logs = self
+            .compress(logs)
+            .map_err(|e| IngestError::Serialization(e.to_string()))?;
 
         match self
             .client
@@ -149,19 +175,26 @@ impl NewRelicLogIngestor {
             Ok(res) => match res.status().as_u16() {
                 202 => {
                     println!("Accepted: the request has been accepted for processing");
+                    Ok(FlushReport {
+                        sent: logs.len(),
+                        dropped: 0,
+                    })
                 }
-                400 => {
-                    eprintln!("Bad request (likely an issue in the payload formatting)");
-                }
-                401 => {
-                    eprintln!("Unauthorized (likely a missing API Key)");
-                }
-                403 => {
-                    eprintln!("Permission issue (likely using an invalid API Key)");
-                }
+                400 => Err(IngestError::Rejected {
+                    status: 400,
+                    message: "Bad request (likely an issue in the payload formatting)".to_string(),
+                }),
+                401 => Err(IngestError::Rejected {
+                    status: 401,
+                    message: "Unauthorized (likely a missing API Key)".to_string(),
+                }),
+                403 => Err(IngestError::Rejected {
+                    status: 403,
+                    message: "Permission issue (likely using an invalid API Key)".to_string(),
+                }),
                 408 => {
                     eprintln!("Request Timeout, request should be retried after some time");
-                    retry().await;
+                    retry().await
                 }
                 413 => {
                     eprintln!("Payload too large (batch is above 1MB uncompressed)");
@@ -169,121 +202,129 @@ impl NewRelicLogIngestor {
                     let logs_len = logs.len();
                     let half = logs_len / 2;
                     let (left, right) = logs.split_at(half);
-                    self.send_logs(left, retries + 1).await;
-                    self.send_logs(right, retries + 1).await;
+                    let left = self.send_logs(left, retries + 1).await;
+                    let right = self.send_logs(right, retries + 1).await;
+                    Ok(left?.merge(right?))
                 }
                 429 => {
                     eprintln!("Too Many Requests, request should be retried after some time");
-                    retry().await;
+                    retry().await
                 }
                 500 => {
                     eprintln!("Internal Server Error, the server encountered an unexpected condition that prevented it from fulfilling the request, request should be retried after some time");
-                    retry().await;
+                    retry().await
                 }
                 503 => {
                     eprintln!("Service Unavailable, the server is not ready to handle the request probably because it is overloaded, request should be retried after some time");
-                    retry().await;
+                    retry().await
                 }
                 _ => {
                     eprintln!("Unknown error, try again later");
-                    retry().await;
+                    retry().await
                 }
             },
-            Err(e) => {
-                eprintln!("Failed to send logs to New Relic: {:?}", e);
-            }
+            Err(e) => Err(IngestError::Transport(e.to_string())),
         }
     }
 
-    #[async_recursion]
-    async fn try_send(&mut self, is_flush: bool) {
-        {
-            // send current logs to new relic if there are any
-            let queue = self.queue.read().await;
-            if queue.is_empty() {
-                return;
-            }
-            if !is_flush {
-                // send the logs only if the last one is more than 5 seconds old
-                // or if the queue has more than MAX_BATCH_SIZE logs
-                let last_log = queue.back().unwrap();
-                let now = Utc::now();
-                let diff = now - last_log.received_at;
-                if diff < chrono::Duration::seconds(MAX_BATCH_DURATION_SECS)
-                    && queue.len() < MAX_BATCH_SIZE
-                {
-                    return;
-                }
+    /// Sends the queued logs in batches of at most `MAX_BATCH_SIZE` logs.
+    /// If `only_full_batches` is set, the last incomplete batch stays in the queue.
+    /// Every batch is sent even if some of them fail, and the first error is returned.
+    async fn try_send(&mut self, only_full_batches: bool) -> Result<FlushReport, IngestError> {
+        let mut report = FlushReport::default();
+        let mut error = None;
+        while !self.queue.is_empty() {
+            if only_full_batches && self.queue.len() < MAX_BATCH_SIZE {
+                break;
             }
-        }
 
-        // get the logs to send
-        let logs = {
-            let mut queue = self.queue.write().await;
+            // get the logs to send
             // TODO: (ROB) this doesn't apply to NR.
             // they only care about the full payload not exceeding 1MB
             // max amount of logs to send at once is 1000
-            let tail = usize::min(queue.len(), MAX_BATCH_SIZE);
-            queue.drain(..tail).map(|e| e.log).collect::<Vec<_>>()
-        };
+            let tail = usize::min(self.queue.len(), MAX_BATCH_SIZE);
+            let logs = self.queue.drain(..tail).collect::<Vec<_>>();
 
-        // send them (retries if it fails)
-        self.send_logs(&logs, 0).await;
-
-        // check if the queue is empty and flush again if it's not
-        let is_queue_empty = { self.queue.read().await.is_empty() };
-        if !is_queue_empty {
-            self.try_send(is_flush).await;
+            // send them (retries if it fails)
+            match self.send_logs(&logs, 0).await {
+                Ok(r) => report = report.merge(r),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
         }
-    }
-}
 
-impl Clone for NewRelicLogIngestor {
-    fn clone(&self) -> Self {
-        Self {
-            url: self.url.clone(),
-            service_name: self.service_name.clone(),
-            api_key: self.api_key.clone(),
-            tags: self.tags.clone(),
-            client: self.client.clone(),
-            queue: self.queue.clone(),
+        if let Some(error) = error {
+            return Err(error);
         }
+        if report.dropped > 0 {
+            return Err(IngestError::Incomplete(report));
+        }
+        Ok(report)
     }
 }
 
 #[async_trait]
-impl LogIngestor for NewRelicLogIngestor {
+impl BatchLogIngestor for NewRelicLogIngestor {
     fn name(&self) -> &'static str {
         "new-relic"
     }
 
-    fn start(&self) {
-        // start a timer that will flush the queue every n seconds
-        let mut this = self.clone();
-        tokio::spawn(async move {
-            let period = Duration::from_secs(MAX_BATCH_DURATION_SECS as u64);
-            let mut interval = tokio::time::interval(period);
-            loop {
-                interval.tick().await;
-                this.try_send(false).await;
-            }
-        });
+    fn flush_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(MAX_BATCH_DURATION_SECS))
     }
 
-    async fn ingest(&mut self, mut log: Log) {
-        // add new relic specific fields
-        log.insert("source".to_string(), json!(NR_SOURCE));
-        log.insert("tags".to_string(), json!(self.tags));
-        log.insert("service".to_string(), json!(self.service_name));
+    async fn ingest_batch(&mut self, logs: Vec<Log>) {
+        for mut log in logs {
+            // add new relic specific fields
+            log.insert("source".to_string(), json!(NR_SOURCE));
+            log.insert("tags".to_string(), json!(self.tags));
+            log.insert("service".to_string(), json!(self.service_name));
+            add_error_attributes(&mut log);
 
-        let log_event = LogEvent {
-            log,
-            received_at: Utc::now(),
-        };
-        self.queue.write().await.push_back(log_event);
+            self.queue.push_back(log);
+        }
+
+        // don't wait for the timer when there are enough logs for a full batch
+        if let Err(e) = self.try_send(true).await {
+            eprintln!("Failed to send a full batch: {e}");
+        }
     }
 
-    async fn flush(&mut self) {
-        self.try_send(true).await;
+    async fn flush(&mut self) -> Result<FlushReport, IngestError> {
+        self.try_send(false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_rejected_batch_is_reported_with_its_status() {
+        let server = httpmock::MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.any_request();
+            then.status(401);
+        });
+        let mut ingestor = NewRelicLogIngestor::new(
+            NewRelicOptions::new("service", "bad-key").with_url(server.base_url()),
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(async {
+            ingestor.ingest_batch(vec![Log::new()]).await;
+            ingestor.flush().await
+        });
+
+        assert!(matches!(
+            result,
+            Err(IngestError::Rejected { status: 401, .. })
+        ));
+        // non-retriable
+        assert_eq!(mock.hits(), 1);
     }
 }
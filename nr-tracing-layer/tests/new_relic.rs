@@ -20,7 +20,7 @@ mod tests {
         std::env::var("NR_API_KEY").unwrap_or("invented_api_key".to_string())
     }
 
-    fn setup(server: &httpmock::MockServer) -> (httpmock::Mock, nr_tracing_layer::LogLayer) {
+    fn setup(server: &httpmock::MockServer) -> (httpmock::Mock<'_>, nr_tracing_layer::LogLayer) {
         dotenvy::from_filename(".env").ok();
         let api_key = get_api_key();
 
@@ -56,6 +56,72 @@ mod tests {
         assert_eq!(mock_server.hits(), 1);
     }
 
+    #[test]
+    fn new_relic_flushes_pending_logs_once_on_shutdown() {
+        let server = httpmock::MockServer::start();
+        let (mock_server, nr) = setup(&server);
+
+        let subscriber = tracing_subscriber::registry().with(nr);
+        let guard = subscriber::set_default(subscriber);
+        log("this is a test message");
+        log("this is another test message");
+        // dropping the subscriber drops the layer, which waits for the final flush
+        drop(guard);
+
+        assert_eq!(mock_server.hits(), 1);
+    }
+
+    #[derive(Debug)]
+    struct StartupError {
+        source: std::io::Error,
+    }
+
+    impl std::fmt::Display for StartupError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "failed to start")
+        }
+    }
+
+    impl std::error::Error for StartupError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.source)
+        }
+    }
+
+    /// Matches a request whose only log has the attributes of a [`StartupError`].
+    fn has_error_attributes(req: &httpmock::prelude::HttpMockRequest) -> bool {
+        let Some(body) = req.body.as_deref() else {
+            return false;
+        };
+        let mut decoder = libflate::gzip::Decoder::new(body).unwrap();
+        let logs: Vec<serde_json::Value> = serde_json::from_reader(&mut decoder).unwrap();
+        let log = &logs[0];
+        log["error.class"] == "StartupError"
+            && log["error.message"] == "failed to start"
+            && log["error.stack"] == "failed to start\nCaused by: config.toml not found"
+    }
+
+    #[test]
+    fn new_relic_maps_errors_to_its_attributes() {
+        let server = httpmock::MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.any_request().matches(has_error_attributes);
+            then.status(202).json_body(serde_json::json!([]));
+        });
+        let options =
+            NewRelicOptions::new("nr-tracing-layer", get_api_key()).with_url(server.base_url());
+        let subscriber = tracing_subscriber::registry().with(nr_tracing_layer::create(options));
+        let guard = subscriber::set_default(subscriber);
+
+        let error = StartupError {
+            source: std::io::Error::new(std::io::ErrorKind::NotFound, "config.toml not found"),
+        };
+        tracing::error!(error = &error as &dyn std::error::Error, "startup");
+        drop(guard);
+
+        assert_eq!(mock.hits(), 1);
+    }
+
     /// This test is just to test manually test the feature
     /// Comment the ignore attribute to run the test and alter the code
     /// as you see fit.